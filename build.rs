@@ -0,0 +1,12 @@
+fn main() {
+    // The printf plugin is only needed to forward the Oodle messages to the
+    // `log` or `tracing` crates.
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    {
+        cc::Build::new()
+            .file("src/printf.c")
+            .compile("oodle_safe_printf");
+
+        println!("cargo:rerun-if-changed=src/printf.c");
+    }
+}
@@ -0,0 +1,216 @@
+use std::alloc::Layout;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use crate::OodleError;
+
+/// Size of the header storing the layout in front of each block given to
+/// Oodle, whose free function only receives the pointer.
+const HEADER_LEN: usize = 2 * std::mem::size_of::<usize>();
+
+/// Allocator used by Oodle for its internal allocations.
+///
+/// The layouts given to the allocator include a small header used to recover
+/// the layout when Oodle frees the memory.
+pub trait OodleAllocator: Send + Sync {
+    /// Allocate a block of memory for `layout`, returning null on failure.
+    fn allocate(&self, layout: Layout) -> *mut u8;
+
+    /// Free a block of memory returned by [OodleAllocator::allocate].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [OodleAllocator::allocate] with the
+    /// same `layout`, and not freed yet.
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout);
+}
+
+/// [OodleAllocator] backed by the Rust global allocator.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GlobalAllocator;
+
+impl OodleAllocator for GlobalAllocator {
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        unsafe { std::alloc::alloc(layout) }
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        std::alloc::dealloc(ptr, layout)
+    }
+}
+
+/// Snapshot of the statistics of a [CountingAllocator].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AllocationStats {
+    /// Bytes currently allocated.
+    pub current_bytes: usize,
+
+    /// Highest value reached by `current_bytes`.
+    pub peak_bytes: usize,
+
+    /// Number of allocations done so far.
+    pub allocations: usize,
+
+    /// Number of deallocations done so far.
+    pub deallocations: usize,
+}
+
+/// [OodleAllocator] forwarding to another allocator while keeping count of
+/// the allocated bytes and of the allocations.
+///
+/// # Example
+///
+/// ```rust
+/// static ALLOCATOR: oodle_safe::CountingAllocator = oodle_safe::CountingAllocator::new();
+///
+/// oodle_safe::set_allocator(&ALLOCATOR).unwrap();
+///
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// oodle_safe::compress_to_vec(
+///     oodle_safe::Compressor::Kraken,
+///     decompressed,
+///     oodle_safe::CompressionLevel::Normal,
+///     None,
+///     None,
+///     None,
+/// )
+/// .unwrap_or_else(|err| panic!("compression failed: {}", err));
+///
+/// let stats = ALLOCATOR.stats();
+/// assert_eq!(stats.current_bytes, 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct CountingAllocator<A: OodleAllocator = GlobalAllocator> {
+    inner: A,
+    current_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    allocations: AtomicUsize,
+    deallocations: AtomicUsize,
+}
+
+impl CountingAllocator {
+    /// Counting allocator backed by the Rust global allocator.
+    pub const fn new() -> Self {
+        Self::with_allocator(GlobalAllocator)
+    }
+}
+
+impl<A: OodleAllocator> CountingAllocator<A> {
+    /// Counting allocator backed by `inner`.
+    pub const fn with_allocator(inner: A) -> Self {
+        Self {
+            inner,
+            current_bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+            allocations: AtomicUsize::new(0),
+            deallocations: AtomicUsize::new(0),
+        }
+    }
+
+    /// Current statistics of the allocator.
+    pub fn stats(&self) -> AllocationStats {
+        AllocationStats {
+            current_bytes: self.current_bytes.load(Ordering::Relaxed),
+            peak_bytes: self.peak_bytes.load(Ordering::Relaxed),
+            allocations: self.allocations.load(Ordering::Relaxed),
+            deallocations: self.deallocations.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reset the peak to the bytes currently allocated.
+    pub fn reset_peak(&self) {
+        let current_bytes = self.current_bytes.load(Ordering::Relaxed);
+        self.peak_bytes.store(current_bytes, Ordering::Relaxed);
+    }
+}
+
+impl<A: OodleAllocator> OodleAllocator for CountingAllocator<A> {
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.allocate(layout);
+
+        if !ptr.is_null() {
+            let current_bytes = self
+                .current_bytes
+                .fetch_add(layout.size(), Ordering::Relaxed);
+            self.peak_bytes
+                .fetch_max(current_bytes + layout.size(), Ordering::Relaxed);
+            self.allocations.fetch_add(1, Ordering::Relaxed);
+        }
+
+        ptr
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.deallocate(ptr, layout);
+
+        self.current_bytes
+            .fetch_sub(layout.size(), Ordering::Relaxed);
+        self.deallocations.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+static ALLOCATOR: OnceLock<&'static dyn OodleAllocator> = OnceLock::new();
+
+/// Route the internal allocations of Oodle through `allocator`.
+///
+/// Oodle does not allow its allocator to be changed, so this must be called
+/// once, before any other Oodle function. Returns
+/// [OodleError::PluginAlreadyInstalled] if an allocator was already set.
+pub fn set_allocator(allocator: &'static dyn OodleAllocator) -> Result<(), OodleError> {
+    ALLOCATOR
+        .set(allocator)
+        .map_err(|_| OodleError::PluginAlreadyInstalled)?;
+
+    unsafe { oodle_sys::OodleCore_Plugins_SetAllocators(Some(malloc_aligned), Some(free)) };
+    Ok(())
+}
+
+/// Layout of the block holding `size` bytes aligned to `alignment`, and offset
+/// of the returned memory in it.
+fn block_layout(size: usize, alignment: usize) -> Option<(Layout, usize)> {
+    let offset = alignment.max(HEADER_LEN);
+    let layout = Layout::from_size_align(size.checked_add(offset)?, alignment).ok()?;
+    Some((layout, offset))
+}
+
+unsafe extern "C" fn malloc_aligned(
+    bytes: oodle_sys::OO_SINTa,
+    alignment: oodle_sys::OO_S32,
+) -> *mut std::ffi::c_void {
+    let allocator = ALLOCATOR.get().unwrap();
+    let (size, alignment) = (bytes as usize, alignment as usize);
+
+    // Oodle expects the allocation to never fail.
+    let Some((layout, offset)) = block_layout(size, alignment) else {
+        std::process::abort();
+    };
+
+    let block = allocator.allocate(layout);
+    if block.is_null() {
+        std::alloc::handle_alloc_error(layout);
+    }
+
+    let ptr = block.add(offset);
+    let header = ptr.sub(HEADER_LEN) as *mut usize;
+    header.write_unaligned(size);
+    header.add(1).write_unaligned(alignment);
+
+    ptr as *mut _
+}
+
+unsafe extern "C" fn free(ptr: *mut std::ffi::c_void) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let allocator = ALLOCATOR.get().unwrap();
+    let ptr = ptr as *mut u8;
+
+    let header = ptr.sub(HEADER_LEN) as *const usize;
+    let size = header.read_unaligned();
+    let alignment = header.add(1).read_unaligned();
+
+    let (layout, offset) = block_layout(size, alignment).unwrap();
+    allocator.deallocate(ptr.sub(offset), layout);
+}
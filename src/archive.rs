@@ -0,0 +1,463 @@
+//! Multi-file archive with a directory of compressed entries.
+//!
+//! Each entry is compressed separately with [compress](crate::compress),
+//! with its own [Compressor] and [CompressionLevel]. Entries with identical
+//! contents share a single copy of the compressed data. The layout is
+//! little-endian:
+//!
+//! | Size          | Field                                                |
+//! |---------------|------------------------------------------------------|
+//! | 4             | Magic number `OODA`                                  |
+//! | 1             | Version, currently 1                                 |
+//! | 3             | Reserved, 0                                          |
+//! | ...           | Compressed data of the entries                       |
+//! | ...           | Directory, one record per entry sorted by path       |
+//! | 8             | Position of the directory                            |
+//! | 4             | Number of entries                                    |
+//! | 4             | Magic number `OODA`                                  |
+//!
+//! A directory record holds the u16 length and the UTF-8 bytes of the path,
+//! the u8 raw `OodleLZ_Compressor` value, the i32 raw `OodleLZ_CompressionLevel`
+//! value, the u64 decompressed size, the u64 position and size of the
+//! compressed data, and the u32 CRC-32 of the decompressed data.
+//!
+//! Archives are written by an [ArchiveBuilder], whose output only depends on
+//! its entries and not on the order they were inserted in.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::bytes::ByteReader;
+use crate::crc32::crc32;
+use crate::{decompress_to_vec, max_decompressed_len, CompressionLevel, Compressor, OodleError};
+
+/// Magic number at the start and the end of an archive.
+pub const MAGIC: [u8; 4] = *b"OODA";
+
+/// Version of the archive format written by this crate.
+pub const VERSION: u8 = 1;
+
+const HEADER_LEN: u64 = 8;
+const FOOTER_LEN: u64 = 16;
+
+/// Entry of the directory of an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Path of the entry in the archive.
+    pub path: String,
+
+    /// Compressor used for the entry.
+    pub compressor: Compressor,
+
+    /// Compression level used for the entry.
+    pub level: CompressionLevel,
+
+    /// Size of the decompressed data of the entry.
+    pub decompressed_len: u64,
+
+    /// Position of the compressed data of the entry in the archive.
+    pub compressed_offset: u64,
+
+    /// Size of the compressed data of the entry.
+    pub compressed_len: u64,
+
+    /// CRC-32 of the decompressed data of the entry.
+    pub checksum: u32,
+}
+
+/// Reader over an archive, giving access to its entries by path.
+///
+/// Works the same on files and on memory, through [std::io::Cursor].
+///
+/// # Example
+///
+/// ```rust
+/// use std::io::Cursor;
+///
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// let mut builder = oodle_safe::archive::ArchiveBuilder::new();
+/// builder
+///     .insert(
+///         "data/decompressed",
+///         decompressed,
+///         oodle_safe::Compressor::Kraken,
+///         oodle_safe::CompressionLevel::Normal,
+///     )
+///     .unwrap_or_else(|err| panic!("compression failed: {}", err));
+///
+/// let mut archive = Vec::new();
+/// builder.write(&mut archive).unwrap();
+///
+/// let mut reader = oodle_safe::archive::ArchiveReader::new(Cursor::new(archive))
+///     .unwrap_or_else(|err| panic!("invalid archive: {}", err));
+///
+/// let output = reader
+///     .read("data/decompressed")
+///     .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+///
+/// assert_eq!(output, decompressed);
+/// ```
+pub struct ArchiveReader<R: Read + Seek> {
+    inner: R,
+
+    /// Entries, sorted by path.
+    entries: Vec<Entry>,
+}
+
+impl<R: Read + Seek> ArchiveReader<R> {
+    /// Create a reader over the archive in `inner`, reading its directory.
+    ///
+    /// Returns an [InvalidData](std::io::ErrorKind::InvalidData) error if the
+    /// archive is malformed or truncated.
+    pub fn new(mut inner: R) -> std::io::Result<Self> {
+        let archive_len = inner.seek(SeekFrom::End(0))?;
+        if archive_len < HEADER_LEN + FOOTER_LEN {
+            return Err(OodleError::InvalidFormat("archive is too short").into());
+        }
+
+        let mut header = [0u8; HEADER_LEN as usize];
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_exact(&mut header)?;
+
+        let mut reader = ByteReader::new(&header);
+        if reader.array::<4>()? != MAGIC {
+            return Err(OodleError::InvalidFormat("bad archive magic").into());
+        }
+        if reader.u8()? != VERSION {
+            return Err(OodleError::InvalidFormat("unsupported archive version").into());
+        }
+
+        let mut footer = [0u8; FOOTER_LEN as usize];
+        let directory_end = archive_len - FOOTER_LEN;
+        inner.seek(SeekFrom::Start(directory_end))?;
+        inner.read_exact(&mut footer)?;
+
+        let mut reader = ByteReader::new(&footer);
+        let directory_offset = reader.u64()?;
+        let num_entries = reader.u32()?;
+        if reader.array::<4>()? != MAGIC {
+            return Err(OodleError::InvalidFormat("bad archive footer").into());
+        }
+
+        if directory_offset < HEADER_LEN || directory_offset > directory_end {
+            return Err(OodleError::InvalidFormat("invalid archive directory position").into());
+        }
+
+        let mut directory = vec![0u8; (directory_end - directory_offset) as usize];
+        inner.seek(SeekFrom::Start(directory_offset))?;
+        inner.read_exact(&mut directory)?;
+
+        let mut reader = ByteReader::new(&directory);
+        let mut entries: Vec<Entry> = Vec::new();
+
+        for _ in 0..num_entries {
+            let path_len = reader.u16()? as usize;
+            let path = std::str::from_utf8(reader.bytes(path_len)?)
+                .map_err(|_| OodleError::InvalidFormat("archive path is not UTF-8"))?
+                .to_owned();
+
+            let compressor = Compressor::try_from(reader.u8()? as oodle_sys::OodleLZ_Compressor)
+                .map_err(|_| OodleError::InvalidFormat("unknown archive compressor"))?;
+            let level = CompressionLevel::try_from(reader.i32()?)
+                .map_err(|_| OodleError::InvalidFormat("unknown archive compression level"))?;
+
+            let entry = Entry {
+                path,
+                compressor,
+                level,
+                decompressed_len: reader.u64()?,
+                compressed_offset: reader.u64()?,
+                compressed_len: reader.u64()?,
+                checksum: reader.u32()?,
+            };
+
+            let in_bounds = entry.compressed_offset >= HEADER_LEN
+                && entry
+                    .compressed_offset
+                    .checked_add(entry.compressed_len)
+                    .is_some_and(|x| x <= directory_offset);
+            if !in_bounds {
+                return Err(OodleError::InvalidFormat("archive entry is out of bounds").into());
+            }
+
+            // Checked before anything is allocated for the entry.
+            let fits = usize::try_from(entry.decompressed_len)
+                .is_ok_and(|x| x <= max_decompressed_len(entry.compressed_len as usize));
+            if !fits {
+                return Err(OodleError::InvalidFormat(
+                    "archive entry length too large for its data",
+                )
+                .into());
+            }
+
+            if entries.last().is_some_and(|x| x.path >= entry.path) {
+                return Err(OodleError::InvalidFormat("archive paths are not sorted").into());
+            }
+
+            entries.push(entry);
+        }
+
+        if reader.remaining() != 0 {
+            return Err(OodleError::InvalidFormat("archive directory has trailing data").into());
+        }
+
+        Ok(Self { inner, entries })
+    }
+
+    /// Entries of the archive, sorted by path.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Entry at `path`, if any.
+    pub fn entry(&self, path: &str) -> Option<&Entry> {
+        self.entries
+            .binary_search_by(|x| x.path.as_str().cmp(path))
+            .ok()
+            .map(|index| &self.entries[index])
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Read the compressed data of the entry at `path`.
+    ///
+    /// Returns a [NotFound](std::io::ErrorKind::NotFound) error if the archive
+    /// has no such entry.
+    pub fn read_compressed(&mut self, path: &str) -> std::io::Result<Vec<u8>> {
+        let entry = self.find(path)?;
+        let (offset, len) = (entry.compressed_offset, entry.compressed_len);
+
+        let mut compressed = vec![0u8; len as usize];
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read_exact(&mut compressed)?;
+
+        Ok(compressed)
+    }
+
+    /// Read and decompress the entry at `path`.
+    ///
+    /// Returns a [NotFound](std::io::ErrorKind::NotFound) error if the archive
+    /// has no such entry, and [OodleError::CrcMismatch] if the decompressed
+    /// data does not match the checksum of the entry.
+    pub fn read(&mut self, path: &str) -> std::io::Result<Vec<u8>> {
+        let compressed = self.read_compressed(path)?;
+        let entry = self.find(path)?;
+
+        let mut decompressed = vec![0u8; entry.decompressed_len as usize];
+        let len = crate::decompress(&compressed, &mut decompressed, None, None, None, None)?;
+
+        if len != decompressed.len() {
+            return Err(OodleError::CorruptData.into());
+        }
+        if crc32(&decompressed) != entry.checksum {
+            return Err(OodleError::CrcMismatch.into());
+        }
+
+        Ok(decompressed)
+    }
+
+    fn find(&self, path: &str) -> std::io::Result<&Entry> {
+        self.entry(path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no archive entry at {}", path),
+            )
+        })
+    }
+}
+
+/// Compressed entry of an [ArchiveBuilder].
+#[derive(Clone)]
+struct PendingEntry {
+    compressor: Compressor,
+    level: CompressionLevel,
+    decompressed_len: u64,
+    checksum: u32,
+    compressed: Vec<u8>,
+}
+
+impl PendingEntry {
+    /// Whether both entries decompress to the same content.
+    fn same_content(&self, other: &PendingEntry) -> Result<bool, OodleError> {
+        if self.decompressed_len != other.decompressed_len || self.checksum != other.checksum {
+            return Ok(false);
+        }
+        if self.compressed == other.compressed {
+            return Ok(true);
+        }
+
+        let len = self.decompressed_len as usize;
+        let content = decompress_to_vec(&self.compressed, len, None, None, None)?;
+        Ok(content == decompress_to_vec(&other.compressed, len, None, None, None)?)
+    }
+}
+
+/// Builder of an archive.
+///
+/// Entries are compressed when inserted, and written sorted by path, so that
+/// building an archive from the same entries always gives the same bytes.
+/// An existing archive can be loaded with [ArchiveBuilder::from_reader] to
+/// add, replace or remove entries, without recompressing the other ones.
+#[derive(Clone, Default)]
+pub struct ArchiveBuilder {
+    entries: BTreeMap<String, PendingEntry>,
+}
+
+impl ArchiveBuilder {
+    /// Create an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder holding the entries of an existing archive, keeping
+    /// their compressed data as is.
+    pub fn from_reader<R: Read + Seek>(reader: &mut ArchiveReader<R>) -> std::io::Result<Self> {
+        let mut entries = BTreeMap::new();
+
+        for entry in reader.entries().to_vec() {
+            let compressed = reader.read_compressed(&entry.path)?;
+            entries.insert(
+                entry.path,
+                PendingEntry {
+                    compressor: entry.compressor,
+                    level: entry.level,
+                    decompressed_len: entry.decompressed_len,
+                    checksum: entry.checksum,
+                    compressed,
+                },
+            );
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Compress `data` and insert it at `path`, replacing any entry already
+    /// there.
+    ///
+    /// When other paths hold the same contents, the data is only stored once
+    /// with the compressor and level of the first of these paths, in sorted
+    /// order, which are then the ones reported for all of them.
+    ///
+    /// Returns [OodleError::InvalidOptions] if the path is empty or longer
+    /// than [u16::MAX] bytes.
+    pub fn insert(
+        &mut self,
+        path: &str,
+        data: &[u8],
+        compressor: Compressor,
+        level: CompressionLevel,
+    ) -> Result<(), OodleError> {
+        if path.is_empty() || path.len() > u16::MAX as usize {
+            return Err(OodleError::InvalidOptions(
+                "archive path must be between 1 and 65535 bytes",
+            ));
+        }
+
+        let compressed = crate::compress_to_vec(compressor, data, level, None, None, None)?;
+
+        self.entries.insert(
+            path.to_owned(),
+            PendingEntry {
+                compressor,
+                level,
+                decompressed_len: data.len() as u64,
+                checksum: crc32(data),
+                compressed,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Remove the entry at `path`, returning whether there was one.
+    pub fn remove(&mut self, path: &str) -> bool {
+        self.entries.remove(path).is_some()
+    }
+
+    /// Whether there is an entry at `path`.
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    /// Paths of the entries, sorted.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the archive to `writer`.
+    pub fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(&MAGIC);
+        header.push(VERSION);
+        header.extend_from_slice(&[0, 0, 0]);
+        writer.write_all(&header)?;
+
+        // Stored entries and their position, by decompressed size and
+        // checksum, written in path order.
+        let mut stored: HashMap<(u64, u32), Vec<(&PendingEntry, u64)>> = HashMap::new();
+        let mut offset = HEADER_LEN;
+        let mut directory = Vec::new();
+
+        for (path, entry) in &self.entries {
+            let candidates = stored
+                .entry((entry.decompressed_len, entry.checksum))
+                .or_default();
+
+            let mut shared = None;
+            for (candidate, candidate_offset) in candidates.iter() {
+                if candidate.same_content(entry)? {
+                    shared = Some((*candidate, *candidate_offset));
+                    break;
+                }
+            }
+
+            let (entry, compressed_offset) = match shared {
+                Some(x) => x,
+                None => {
+                    writer.write_all(&entry.compressed)?;
+                    candidates.push((entry, offset));
+                    offset += entry.compressed.len() as u64;
+                    (entry, offset - entry.compressed.len() as u64)
+                }
+            };
+
+            let compressor: oodle_sys::OodleLZ_Compressor = entry.compressor.into();
+            let level: oodle_sys::OodleLZ_CompressionLevel = entry.level.into();
+
+            directory.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            directory.extend_from_slice(path.as_bytes());
+            directory.push(compressor as u8);
+            directory.extend_from_slice(&level.to_le_bytes());
+            directory.extend_from_slice(&entry.decompressed_len.to_le_bytes());
+            directory.extend_from_slice(&compressed_offset.to_le_bytes());
+            directory.extend_from_slice(&(entry.compressed.len() as u64).to_le_bytes());
+            directory.extend_from_slice(&entry.checksum.to_le_bytes());
+        }
+
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        directory.extend_from_slice(&MAGIC);
+        writer.write_all(&directory)?;
+
+        writer.flush()
+    }
+}
@@ -0,0 +1,89 @@
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Progress of a decompression, reported to the callback of
+/// [decompress_with_callback](crate::decompress_with_callback).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecompressProgress {
+    /// Total size of the decompressed data.
+    pub decompressed_len: usize,
+
+    /// Number of bytes decompressed so far.
+    pub decompressed_done: usize,
+
+    /// Size of the compressed buffer.
+    pub compressed_len: usize,
+
+    /// Number of compressed bytes consumed so far.
+    pub compressed_used: usize,
+}
+
+/// Whether the decompression should go on after a callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallbackAction {
+    /// Continue the decompression
+    Continue,
+
+    /// Stop the decompression, which returns [OodleError::Cancelled](crate::OodleError::Cancelled)
+    Cancel,
+}
+
+/// State shared with [decompress_callback] through the Oodle user data.
+pub(crate) struct CallbackData<'a> {
+    callback: &'a mut dyn FnMut(DecompressProgress) -> CallbackAction,
+    pub(crate) cancelled: bool,
+    pub(crate) panic: Option<Box<dyn Any + Send>>,
+}
+
+impl<'a> CallbackData<'a> {
+    pub(crate) fn new(callback: &'a mut dyn FnMut(DecompressProgress) -> CallbackAction) -> Self {
+        Self {
+            callback,
+            cancelled: false,
+            panic: None,
+        }
+    }
+}
+
+/// Oodle decompression callback forwarding to the closure in [CallbackData].
+///
+/// Panics are caught here, as they must not unwind through Oodle. The
+/// decompression is cancelled and the panic is stored to be resumed once
+/// Oodle has returned.
+pub(crate) unsafe extern "C" fn decompress_callback(
+    userdata: *mut std::ffi::c_void,
+    _raw_buf: *const u8,
+    raw_len: isize,
+    _comp_buf: *const u8,
+    comp_buffer_size: isize,
+    raw_done: isize,
+    comp_used: isize,
+) -> oodle_sys::OodleDecompressCallbackRet {
+    let data = &mut *(userdata as *mut CallbackData);
+
+    if data.cancelled {
+        return oodle_sys::OodleDecompressCallbackRet_OodleDecompressCallbackRet_Cancel;
+    }
+
+    let progress = DecompressProgress {
+        decompressed_len: raw_len as usize,
+        decompressed_done: raw_done as usize,
+        compressed_len: comp_buffer_size as usize,
+        compressed_used: comp_used as usize,
+    };
+
+    match catch_unwind(AssertUnwindSafe(|| (data.callback)(progress))) {
+        Ok(CallbackAction::Continue) => {
+            oodle_sys::OodleDecompressCallbackRet_OodleDecompressCallbackRet_Continue
+        }
+        Ok(CallbackAction::Cancel) => {
+            data.cancelled = true;
+            oodle_sys::OodleDecompressCallbackRet_OodleDecompressCallbackRet_Cancel
+        }
+        Err(payload) => {
+            data.cancelled = true;
+            data.panic = Some(payload);
+            oodle_sys::OodleDecompressCallbackRet_OodleDecompressCallbackRet_Cancel
+        }
+    }
+}
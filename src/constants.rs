@@ -1,7 +1,7 @@
 /// The number of raw bytes per seek chunk
 pub const BLOCK_LEN: u32 = oodle_sys::OODLELZ_BLOCK_LEN;
 
-/// Return value of [compress] and [decompress] on failure
+/// Return value of the Oodle compression and decompression functions on failure
 pub const FAILED: u32 = oodle_sys::OODLELZ_FAILED;
 
 /// Maximum value of max_local_dictionary_size in CompressOptions
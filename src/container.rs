@@ -0,0 +1,493 @@
+//! Multi-block container for data of any size.
+//!
+//! The data is split into blocks which are compressed separately with
+//! [compress](crate::compress), so that neither a single call nor a length
+//! prefix has to describe the whole data. The layout is little-endian:
+//!
+//! | Size          | Field                                                |
+//! |---------------|------------------------------------------------------|
+//! | 4             | Magic number `OODC`                                  |
+//! | 1             | Version, currently 1                                 |
+//! | 1             | Raw `OodleLZ_Compressor` value                       |
+//! | 2             | Reserved, 0                                          |
+//! | 4             | Block length, the decompressed size of each block    |
+//! | ...           | Compressed blocks                                    |
+//! | 8 per block   | Block index: u32 decompressed and compressed sizes   |
+//! | 8             | Number of blocks                                     |
+//! | 4             | Magic number `OODC`                                  |
+//!
+//! The index is written after the blocks, so that [ContainerWriter] can
+//! stream its output, and [ContainerReader] finds it from the end of the
+//! container.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::bytes::ByteReader;
+use crate::{
+    max_decompressed_len, CheckCRC, CompressOptions, CompressionLevel, Compressor, OodleError,
+};
+
+/// Magic number at the start and the end of a container.
+pub const MAGIC: [u8; 4] = *b"OODC";
+
+/// Version of the container format written by this crate.
+pub const VERSION: u8 = 1;
+
+/// Default decompressed size of the blocks of a container.
+pub const DEFAULT_BLOCK_LEN: u32 = 16 << 20;
+
+const HEADER_LEN: u64 = 12;
+const FOOTER_LEN: u64 = 12;
+const INDEX_ENTRY_LEN: u64 = 8;
+
+/// Position and size of a block of a container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockInfo {
+    /// Position of the block in the decompressed data.
+    pub decompressed_offset: u64,
+
+    /// Size of the decompressed data of the block.
+    pub decompressed_len: u32,
+
+    /// Position of the block in the container.
+    pub compressed_offset: u64,
+
+    /// Size of the compressed data of the block.
+    pub compressed_len: u32,
+}
+
+/// Compressor writing a container to any [Write] destination.
+///
+/// Incoming data is buffered into blocks of `block_len` bytes, and each full
+/// block is compressed and written when more data comes in. The container
+/// must be completed with [ContainerWriter::finish], which writes the last
+/// block and the block index.
+///
+/// # Example
+///
+/// ```rust
+/// use std::io::{Cursor, Read, Write};
+///
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// let mut writer = oodle_safe::container::ContainerWriter::new(
+///     Vec::new(),
+///     oodle_safe::Compressor::Kraken,
+///     oodle_safe::CompressionLevel::Normal,
+///     None,
+///     None,
+/// )
+/// .unwrap_or_else(|err| panic!("invalid writer: {}", err));
+///
+/// writer.write_all(decompressed).unwrap();
+/// let container = writer.finish().unwrap();
+///
+/// let mut reader = oodle_safe::container::ContainerReader::new(Cursor::new(container))
+///     .unwrap_or_else(|err| panic!("invalid container: {}", err));
+///
+/// let mut output = Vec::new();
+/// reader.read_to_end(&mut output).unwrap();
+///
+/// assert_eq!(output, decompressed);
+/// ```
+pub struct ContainerWriter<W: Write> {
+    inner: Option<W>,
+    compressor: Compressor,
+    level: CompressionLevel,
+    options: CompressOptions,
+    block_len: usize,
+    buffer: Vec<u8>,
+    compressed: Vec<u8>,
+
+    /// Decompressed and compressed sizes of the blocks written so far.
+    index: Vec<(u32, u32)>,
+}
+
+impl<W: Write> ContainerWriter<W> {
+    /// Create a writer compressing to `inner` in blocks of `block_len` bytes,
+    /// writing the container header right away.
+    ///
+    /// When `options` is `None`, [CompressOptions::for_compressor] is used.
+    /// When `block_len` is `None`, [DEFAULT_BLOCK_LEN] is used.
+    pub fn new(
+        mut inner: W,
+        compressor: Compressor,
+        level: CompressionLevel,
+        options: Option<CompressOptions>,
+        block_len: Option<u32>,
+    ) -> std::io::Result<Self> {
+        let options = options.unwrap_or_else(|| CompressOptions::for_compressor(compressor, level));
+        options.check()?;
+
+        let block_len = block_len.unwrap_or(DEFAULT_BLOCK_LEN);
+        if block_len == 0 {
+            return Err(OodleError::InvalidOptions("block_len must not be 0").into());
+        }
+
+        // The index stores the compressed size of each block as a u32.
+        if u32::try_from(crate::compressed_buffer_size_needed(
+            compressor,
+            block_len as usize,
+        ))
+        .is_err()
+        {
+            return Err(OodleError::InvalidOptions("block_len is too large").into());
+        }
+
+        let raw_compressor: oodle_sys::OodleLZ_Compressor = compressor.into();
+
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(&MAGIC);
+        header.push(VERSION);
+        header.push(raw_compressor as u8);
+        header.extend_from_slice(&[0, 0]);
+        header.extend_from_slice(&block_len.to_le_bytes());
+        inner.write_all(&header)?;
+
+        Ok(Self {
+            inner: Some(inner),
+            compressor,
+            level,
+            options,
+            block_len: block_len as usize,
+            buffer: Vec::new(),
+            compressed: Vec::new(),
+            index: Vec::new(),
+        })
+    }
+
+    /// Number of bytes written to the compressor so far.
+    pub fn decompressed_len(&self) -> u64 {
+        let written: u64 = self.index.iter().map(|(x, _)| *x as u64).sum();
+        written + self.buffer.len() as u64
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Get a mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Compress the buffered block and write it to the inner writer.
+    fn write_block(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        self.compressed.resize(
+            crate::compressed_buffer_size_needed(self.compressor, self.buffer.len()),
+            0,
+        );
+
+        let compressed_len = crate::compress(
+            self.compressor,
+            &self.buffer,
+            &mut self.compressed,
+            self.level,
+            Some(self.options),
+            None,
+            None,
+        )?;
+
+        self.inner
+            .as_mut()
+            .unwrap()
+            .write_all(&self.compressed[..compressed_len])?;
+        self.index
+            .push((self.buffer.len() as u32, compressed_len as u32));
+        self.buffer.clear();
+
+        Ok(())
+    }
+
+    /// Compress the remaining data, write the block index and return the
+    /// inner writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.write_block()?;
+
+        let mut footer =
+            Vec::with_capacity(self.index.len() * INDEX_ENTRY_LEN as usize + FOOTER_LEN as usize);
+        for (decompressed_len, compressed_len) in &self.index {
+            footer.extend_from_slice(&decompressed_len.to_le_bytes());
+            footer.extend_from_slice(&compressed_len.to_le_bytes());
+        }
+        footer.extend_from_slice(&(self.index.len() as u64).to_le_bytes());
+        footer.extend_from_slice(&MAGIC);
+
+        let mut inner = self.inner.take().unwrap();
+        inner.write_all(&footer)?;
+        inner.flush()?;
+
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for ContainerWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.buffer.capacity() == 0 {
+            self.buffer.reserve_exact(self.block_len);
+        }
+
+        // The full block is only written before taking more data, so that a
+        // failure leaves `buf` unconsumed and the block buffered for a retry.
+        if self.buffer.len() == self.block_len {
+            self.write_block()?;
+        }
+
+        let len = buf.len().min(self.block_len - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+
+        Ok(len)
+    }
+
+    /// Flush the inner writer.
+    ///
+    /// The buffered partial block is not compressed, as only the last block
+    /// of the container may be shorter than `block_len`.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+/// Random-access decompressor over a container, implementing [Read] and
+/// [Seek] over the decompressed positions.
+///
+/// Works the same on files and on memory, through [std::io::Cursor]. Only the
+/// blocks touched by a read are decoded, and the last one is kept for the
+/// following reads.
+pub struct ContainerReader<R: Read + Seek> {
+    inner: R,
+    compressor: Compressor,
+    block_len: u32,
+    blocks: Vec<BlockInfo>,
+    decompressed_len: u64,
+    check_crc: CheckCRC,
+
+    /// Decompressed position of the next byte to return to the reader.
+    pos: u64,
+
+    /// Last decoded block.
+    block: Option<(usize, Vec<u8>)>,
+
+    /// Compressed data of the block being decoded.
+    compressed: Vec<u8>,
+}
+
+impl<R: Read + Seek> ContainerReader<R> {
+    /// Create a reader over the container in `inner`, reading its header and
+    /// block index.
+    ///
+    /// Returns an [InvalidData](std::io::ErrorKind::InvalidData) error if the
+    /// container is malformed or truncated.
+    pub fn new(mut inner: R) -> std::io::Result<Self> {
+        let container_len = inner.seek(SeekFrom::End(0))?;
+        if container_len < HEADER_LEN + FOOTER_LEN {
+            return Err(OodleError::InvalidFormat("container is too short").into());
+        }
+
+        let mut header = [0u8; HEADER_LEN as usize];
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_exact(&mut header)?;
+
+        let mut reader = ByteReader::new(&header);
+        if reader.array::<4>()? != MAGIC {
+            return Err(OodleError::InvalidFormat("bad container magic").into());
+        }
+        if reader.u8()? != VERSION {
+            return Err(OodleError::InvalidFormat("unsupported container version").into());
+        }
+        let compressor = Compressor::try_from(reader.u8()? as oodle_sys::OodleLZ_Compressor)
+            .map_err(|_| OodleError::InvalidFormat("unknown container compressor"))?;
+        let _reserved = reader.array::<2>()?;
+        let block_len = reader.u32()?;
+
+        let mut footer = [0u8; FOOTER_LEN as usize];
+        inner.seek(SeekFrom::Start(container_len - FOOTER_LEN))?;
+        inner.read_exact(&mut footer)?;
+
+        let mut reader = ByteReader::new(&footer);
+        let num_blocks = reader.u64()?;
+        if reader.array::<4>()? != MAGIC {
+            return Err(OodleError::InvalidFormat("bad container footer").into());
+        }
+
+        let index_offset = num_blocks
+            .checked_mul(INDEX_ENTRY_LEN)
+            .and_then(|x| (container_len - FOOTER_LEN).checked_sub(x))
+            .filter(|x| *x >= HEADER_LEN)
+            .ok_or(OodleError::InvalidFormat("container index is truncated"))?;
+
+        let mut index = vec![0u8; (num_blocks * INDEX_ENTRY_LEN) as usize];
+        inner.seek(SeekFrom::Start(index_offset))?;
+        inner.read_exact(&mut index)?;
+
+        let mut reader = ByteReader::new(&index);
+        let mut blocks = Vec::with_capacity(num_blocks as usize);
+        let mut decompressed_offset = 0;
+        let mut compressed_offset = HEADER_LEN;
+
+        for _ in 0..num_blocks {
+            let decompressed_len = reader.u32()?;
+            let compressed_len = reader.u32()?;
+
+            if decompressed_len == 0 || decompressed_len > block_len {
+                return Err(OodleError::InvalidFormat("invalid container block size").into());
+            }
+
+            // Checked before anything is allocated for the block.
+            if decompressed_len as usize > max_decompressed_len(compressed_len as usize) {
+                return Err(OodleError::InvalidFormat(
+                    "container block size too large for its data",
+                )
+                .into());
+            }
+
+            blocks.push(BlockInfo {
+                decompressed_offset,
+                decompressed_len,
+                compressed_offset,
+                compressed_len,
+            });
+
+            decompressed_offset += decompressed_len as u64;
+            compressed_offset += compressed_len as u64;
+        }
+
+        if compressed_offset != index_offset {
+            return Err(
+                OodleError::InvalidFormat("container blocks do not match the index").into(),
+            );
+        }
+
+        Ok(Self {
+            inner,
+            compressor,
+            block_len,
+            blocks,
+            decompressed_len: decompressed_offset,
+            check_crc: CheckCRC::No,
+            pos: 0,
+            block: None,
+            compressed: Vec::new(),
+        })
+    }
+
+    /// Whether to check the quantum CRCs of the compressed data.
+    pub fn set_check_crc(&mut self, check_crc: CheckCRC) {
+        self.check_crc = check_crc;
+    }
+
+    /// Compressor used for the blocks.
+    pub fn compressor(&self) -> Compressor {
+        self.compressor
+    }
+
+    /// Decompressed size of each block but the last one.
+    pub fn block_len(&self) -> u32 {
+        self.block_len
+    }
+
+    /// Position and size of each block, in order.
+    pub fn blocks(&self) -> &[BlockInfo] {
+        &self.blocks
+    }
+
+    /// Total size of the decompressed data.
+    pub fn decompressed_len(&self) -> u64 {
+        self.decompressed_len
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Decoded data of the block `index`, decoding it if it is not the last
+    /// decoded one.
+    fn block(&mut self, index: usize) -> std::io::Result<&[u8]> {
+        if !matches!(&self.block, Some((x, _)) if *x == index) {
+            let info = self.blocks[index];
+
+            self.compressed.resize(info.compressed_len as usize, 0);
+            self.inner.seek(SeekFrom::Start(info.compressed_offset))?;
+            self.inner.read_exact(&mut self.compressed)?;
+
+            let mut decompressed = self.block.take().map(|(_, x)| x).unwrap_or_default();
+            decompressed.resize(info.decompressed_len as usize, 0);
+
+            let len = crate::decompress(
+                &self.compressed,
+                &mut decompressed,
+                None,
+                Some(self.check_crc),
+                None,
+                None,
+            )?;
+            if len != decompressed.len() {
+                return Err(OodleError::CorruptData.into());
+            }
+
+            self.block = Some((index, decompressed));
+        }
+
+        Ok(&self.block.as_ref().unwrap().1)
+    }
+}
+
+impl<R: Read + Seek> Read for ContainerReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.decompressed_len {
+            return Ok(0);
+        }
+
+        let index = self
+            .blocks
+            .partition_point(|x| x.decompressed_offset <= self.pos)
+            - 1;
+        let offset = (self.pos - self.blocks[index].decompressed_offset) as usize;
+        let block = &self.block(index)?[offset..];
+
+        let len = block.len().min(buf.len());
+        buf[..len].copy_from_slice(&block[..len]);
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for ContainerReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(x) => {
+                self.pos = x;
+                return Ok(x);
+            }
+            SeekFrom::End(x) => (self.decompressed_len, x),
+            SeekFrom::Current(x) => (self.pos, x),
+        };
+
+        match base.checked_add_signed(offset) {
+            Some(x) => {
+                self.pos = x;
+                Ok(x)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{decompress_impl, CheckCRC, OodleError, Verbosity};
+
+/// Reusable decompressor that owns the memory of the Oodle decoder.
+///
+/// The decoder memory is allocated once with `OodleLZDecoder_MemorySizeNeeded`
+/// for the largest output, and passed to Oodle on every call to
+/// [Decoder::decode], so that Oodle does not allocate for each decompression.
+///
+/// The memory is sized for any compressor, as the chunks of a stream may use
+/// different ones, like those compressed with [Hydra](crate::Compressor::Hydra).
+///
+/// A decoder can be moved to another thread, so each worker thread can own
+/// one.
+///
+/// # Example
+///
+/// ```rust
+/// let compressed = include_bytes!("../test_data/compressed");
+/// # let decompressed_size = u32::from_le_bytes(compressed[..4].try_into().unwrap()) as usize;
+///
+/// let mut decoder = oodle_safe::Decoder::new(decompressed_size);
+///
+/// let mut decompressed = vec![0u8; decompressed_size];
+/// decoder
+///     .decode(&compressed[4..], &mut decompressed, None, None)
+///     .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+/// ```
+pub struct Decoder {
+    max_decompressed_len: usize,
+    memory: Vec<u8>,
+}
+
+impl Decoder {
+    /// Create a decoder decompressing to at most `max_decompressed_len`
+    /// bytes.
+    pub fn new(max_decompressed_len: usize) -> Self {
+        let memory_len = unsafe {
+            oodle_sys::OodleLZDecoder_MemorySizeNeeded(
+                oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Invalid,
+                max_decompressed_len as isize,
+            )
+        };
+
+        Self {
+            max_decompressed_len,
+            memory: vec![0u8; memory_len.max(0) as usize],
+        }
+    }
+
+    /// Largest output the decoder accepts.
+    pub fn max_decompressed_len(&self) -> usize {
+        self.max_decompressed_len
+    }
+
+    /// Size of the decoder memory.
+    pub fn memory_len(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// Decompress `compressed` into `decompressed`, see [decompress](crate::decompress).
+    ///
+    /// Returns [OodleError::InputTooLarge] if `decompressed` is larger than
+    /// [Decoder::max_decompressed_len].
+    pub fn decode(
+        &mut self,
+        compressed: &[u8],
+        decompressed: &mut [u8],
+        check_crc: Option<CheckCRC>,
+        verbosity: Option<Verbosity>,
+    ) -> Result<usize, OodleError> {
+        if decompressed.len() > self.max_decompressed_len {
+            return Err(OodleError::InputTooLarge {
+                max: self.max_decompressed_len,
+                provided: decompressed.len(),
+            });
+        }
+
+        decompress_impl(
+            compressed,
+            decompressed,
+            None,
+            check_crc,
+            verbosity,
+            None,
+            None,
+            Some(&mut self.memory),
+        )
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{compress, CompressOptions, CompressionLevel, Compressor, OodleError};
+
+/// Amount of scratch memory needed to compress `decompressed_len` bytes
+/// without any allocation from Oodle.
+///
+/// When Oodle has no worst-case bound for the compressor and level, the
+/// typical bound is returned instead, with which allocations are still
+/// possible but rare. Returns `None` if no bound is known at all.
+pub fn compress_scratch_mem_bound(
+    compressor: Compressor,
+    level: CompressionLevel,
+    decompressed_len: usize,
+    options: Option<CompressOptions>,
+) -> Option<usize> {
+    let options: oodle_sys::OodleLZ_CompressOptions = options
+        .unwrap_or_else(|| CompressOptions::for_compressor(compressor, level))
+        .into();
+
+    let bound = |bound_type| unsafe {
+        oodle_sys::OodleLZ_GetCompressScratchMemBoundEx(
+            compressor.into(),
+            level.into(),
+            bound_type,
+            decompressed_len as isize,
+            &options,
+        )
+    };
+
+    [
+        oodle_sys::OodleLZ_CompressScratchMemBoundType_OodleLZ_CompressScratchMemBoundType_WorstCase,
+        oodle_sys::OodleLZ_CompressScratchMemBoundType_OodleLZ_CompressScratchMemBoundType_Typical,
+    ]
+    .into_iter()
+    .map(bound)
+    .find(|&x| x != oodle_sys::OODLELZ_SCRATCH_MEM_NO_BOUND as isize)
+    .map(|x| x as usize)
+}
+
+/// Reusable compressor that owns its scratch memory.
+///
+/// The scratch memory is sized once with [compress_scratch_mem_bound] for the
+/// largest input the encoder accepts, and reused on every call to
+/// [Encoder::encode], so that Oodle does not allocate for each compression.
+///
+/// # Example
+///
+/// ```rust
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// let mut encoder = oodle_safe::Encoder::new(
+///     oodle_safe::Compressor::Kraken,
+///     oodle_safe::CompressionLevel::Normal,
+///     None,
+///     decompressed.len(),
+/// )
+/// .unwrap_or_else(|err| panic!("invalid encoder: {}", err));
+///
+/// let compressed = encoder
+///     .encode_to_vec(decompressed)
+///     .unwrap_or_else(|err| panic!("compression failed: {}", err));
+/// ```
+pub struct Encoder {
+    compressor: Compressor,
+    level: CompressionLevel,
+    options: CompressOptions,
+    max_decompressed_len: usize,
+    scratch_memory: Vec<u8>,
+}
+
+impl Encoder {
+    /// Create an encoder for inputs of at most `max_decompressed_len` bytes.
+    ///
+    /// When `options` is `None`, [CompressOptions::for_compressor] is used.
+    pub fn new(
+        compressor: Compressor,
+        level: CompressionLevel,
+        options: Option<CompressOptions>,
+        max_decompressed_len: usize,
+    ) -> Result<Self, OodleError> {
+        let options = match options {
+            Some(x) => {
+                x.check()?;
+                x
+            }
+            None => CompressOptions::for_compressor(compressor, level),
+        };
+
+        let scratch_memory_len =
+            compress_scratch_mem_bound(compressor, level, max_decompressed_len, Some(options))
+                .unwrap_or(0);
+
+        Ok(Self {
+            compressor,
+            level,
+            options,
+            max_decompressed_len,
+            scratch_memory: vec![0u8; scratch_memory_len],
+        })
+    }
+
+    /// Compressor used by the encoder.
+    pub fn compressor(&self) -> Compressor {
+        self.compressor
+    }
+
+    /// Compression level used by the encoder.
+    pub fn level(&self) -> CompressionLevel {
+        self.level
+    }
+
+    /// Compression options used by the encoder.
+    pub fn options(&self) -> CompressOptions {
+        self.options
+    }
+
+    /// Largest input the encoder accepts.
+    pub fn max_decompressed_len(&self) -> usize {
+        self.max_decompressed_len
+    }
+
+    /// Size of the scratch memory owned by the encoder.
+    pub fn scratch_memory_len(&self) -> usize {
+        self.scratch_memory.len()
+    }
+
+    /// Compress `decompressed` into `compressed`, see [compress].
+    ///
+    /// Returns [OodleError::InputTooLarge] if `decompressed` is larger than
+    /// [Encoder::max_decompressed_len].
+    pub fn encode(
+        &mut self,
+        decompressed: &[u8],
+        compressed: &mut [u8],
+    ) -> Result<usize, OodleError> {
+        if decompressed.len() > self.max_decompressed_len {
+            return Err(OodleError::InputTooLarge {
+                max: self.max_decompressed_len,
+                provided: decompressed.len(),
+            });
+        }
+
+        let scratch_memory = if self.scratch_memory.is_empty() {
+            None
+        } else {
+            Some(self.scratch_memory.as_mut_slice())
+        };
+
+        compress(
+            self.compressor,
+            decompressed,
+            compressed,
+            self.level,
+            Some(self.options),
+            None,
+            scratch_memory,
+        )
+    }
+
+    /// Compress `decompressed` into a newly allocated vector, see
+    /// [Encoder::encode].
+    pub fn encode_to_vec(&mut self, decompressed: &[u8]) -> Result<Vec<u8>, OodleError> {
+        let mut compressed =
+            vec![0u8; crate::compressed_buffer_size_needed(self.compressor, decompressed.len())];
+
+        let compressed_size = self.encode(decompressed, &mut compressed)?;
+
+        compressed.truncate(compressed_size);
+        Ok(compressed)
+    }
+}
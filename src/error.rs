@@ -0,0 +1,114 @@
+/// Errors that can occur when using the Oodle library.
+///
+/// Oodle itself only reports failure with [FAILED](crate::FAILED), so most of these variants
+/// come from checks done before calling into the library, or from inspecting
+/// the compressed data after a failed call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OodleError {
+    /// The output buffer is smaller than what the operation may write.
+    BufferTooSmall { required: usize, provided: usize },
+
+    /// The input is larger than what the operation was set up for.
+    InputTooLarge { max: usize, provided: usize },
+
+    /// The compression options break one of their documented constraints, or
+    /// would be rewritten by
+    /// [CompressOptions::validate](crate::CompressOptions::validate).
+    InvalidOptions(&'static str),
+
+    /// The dictionary is not contiguous with the data, or its length is not a
+    /// multiple of [BLOCK_LEN](crate::BLOCK_LEN).
+    InvalidDictionary,
+
+    /// The compressed data depends on a dictionary that was not provided.
+    MissingDictionary,
+
+    /// The compressed data does not start with a valid Oodle chunk header.
+    InvalidHeader,
+
+    /// The compressed data failed its quantum CRC check.
+    CrcMismatch,
+
+    /// The compressed data is corrupt or does not decode to the expected size.
+    CorruptData,
+
+    /// Serialized data, like a seek table, is malformed.
+    InvalidFormat(&'static str),
+
+    /// A raw value or a name does not match any variant of the named enum.
+    UnknownValue(&'static str),
+
+    /// An Oodle plugin which can only be installed once was already installed.
+    PluginAlreadyInstalled,
+
+    /// Oodle raised an assertion, with the `Error` assertion action of the
+    /// `log` and `tracing` features.
+    AssertionFailed,
+
+    /// The decompression was cancelled by its callback.
+    Cancelled,
+
+    /// The compressor failed for a reason that could not be determined.
+    CompressionFailed,
+
+    /// Oodle could not create a streaming decoder.
+    DecoderCreationFailed,
+}
+
+impl std::fmt::Display for OodleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OodleError::BufferTooSmall { required, provided } => write!(
+                f,
+                "output buffer too small: {} bytes required, {} provided",
+                required, provided
+            ),
+            OodleError::InputTooLarge { max, provided } => write!(
+                f,
+                "input too large: {} bytes provided, at most {} supported",
+                provided, max
+            ),
+            OodleError::InvalidOptions(reason) => {
+                write!(f, "invalid compression options: {}", reason)
+            }
+            OodleError::InvalidDictionary => write!(f, "invalid dictionary"),
+            OodleError::MissingDictionary => write!(f, "compressed data requires a dictionary"),
+            OodleError::InvalidHeader => write!(f, "invalid compressed data header"),
+            OodleError::CrcMismatch => write!(f, "compressed data CRC mismatch"),
+            OodleError::CorruptData => write!(f, "corrupt compressed data"),
+            OodleError::InvalidFormat(reason) => write!(f, "invalid format: {}", reason),
+            OodleError::UnknownValue(kind) => write!(f, "unknown {}", kind),
+            OodleError::PluginAlreadyInstalled => write!(f, "plugin already installed"),
+            OodleError::AssertionFailed => write!(f, "oodle assertion failed"),
+            OodleError::Cancelled => write!(f, "decompression cancelled"),
+            OodleError::CompressionFailed => write!(f, "compression failed"),
+            OodleError::DecoderCreationFailed => write!(f, "failed to create the decoder"),
+        }
+    }
+}
+
+impl std::error::Error for OodleError {}
+
+impl From<OodleError> for std::io::Error {
+    fn from(err: OodleError) -> Self {
+        let kind = match err {
+            OodleError::BufferTooSmall { .. }
+            | OodleError::InputTooLarge { .. }
+            | OodleError::InvalidOptions(_)
+            | OodleError::InvalidDictionary
+            | OodleError::MissingDictionary
+            | OodleError::UnknownValue(_) => std::io::ErrorKind::InvalidInput,
+            OodleError::InvalidHeader
+            | OodleError::CrcMismatch
+            | OodleError::CorruptData
+            | OodleError::InvalidFormat(_) => std::io::ErrorKind::InvalidData,
+            OodleError::PluginAlreadyInstalled
+            | OodleError::AssertionFailed
+            | OodleError::Cancelled
+            | OodleError::CompressionFailed
+            | OodleError::DecoderCreationFailed => std::io::ErrorKind::Other,
+        };
+
+        std::io::Error::new(kind, err)
+    }
+}
@@ -0,0 +1,271 @@
+//! Self-describing frame format for compressed data.
+//!
+//! A frame starts with a little-endian header holding everything needed to
+//! decompress it, followed by the compressed data:
+//!
+//! | Offset | Size | Field                                               |
+//! |--------|------|-----------------------------------------------------|
+//! | 0      | 4    | Magic number `OODF`                                 |
+//! | 4      | 1    | Version, currently 1                                |
+//! | 5      | 1    | Flags: seek chunk reset, quantum CRCs, checksum     |
+//! | 6      | 1    | Raw `OodleLZ_Compressor` value                      |
+//! | 7      | 1    | Reserved, 0                                         |
+//! | 8      | 8    | Decompressed length                                 |
+//! | 16     | 4    | CRC-32 of the decompressed data, if flagged         |
+//!
+//! The legacy layout, a little-endian u32 decompressed length followed by the
+//! compressed data, can be read with [decompress_legacy] and [legacy_reader].
+
+use std::io::Read;
+
+use crate::bytes::ByteReader;
+use crate::crc32::crc32;
+use crate::{
+    compress_to_vec, decompress, max_decompressed_len, CheckCRC, CompressOptions, CompressionLevel,
+    Compressor, DecompressReader, OodleError,
+};
+
+/// Magic number at the start of a frame.
+pub const MAGIC: [u8; 4] = *b"OODF";
+
+/// Version of the frame format written by this crate.
+pub const VERSION: u8 = 1;
+
+const FLAG_SEEK_CHUNK_RESET: u8 = 1 << 0;
+const FLAG_QUANTUM_CRCS: u8 = 1 << 1;
+const FLAG_CHECKSUM: u8 = 1 << 2;
+
+/// Header of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Compressor used for the data.
+    pub compressor: Compressor,
+
+    /// Size of the decompressed data.
+    pub decompressed_len: u64,
+
+    /// Whether the data was compressed with independent seek chunks.
+    pub seek_chunk_reset: bool,
+
+    /// Whether the data holds quantum CRCs, which are checked on
+    /// decompression.
+    pub quantum_crcs: bool,
+
+    /// CRC-32 of the decompressed data, checked on decompression.
+    pub checksum: Option<u32>,
+}
+
+impl FrameHeader {
+    /// Size of the serialized header.
+    pub fn encoded_len(&self) -> usize {
+        if self.checksum.is_some() {
+            20
+        } else {
+            16
+        }
+    }
+
+    /// Serialize the header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.seek_chunk_reset {
+            flags |= FLAG_SEEK_CHUNK_RESET;
+        }
+        if self.quantum_crcs {
+            flags |= FLAG_QUANTUM_CRCS;
+        }
+        if self.checksum.is_some() {
+            flags |= FLAG_CHECKSUM;
+        }
+
+        let compressor: oodle_sys::OodleLZ_Compressor = self.compressor.into();
+
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.push(flags);
+        bytes.push(compressor as u8);
+        bytes.push(0);
+        bytes.extend_from_slice(&self.decompressed_len.to_le_bytes());
+        if let Some(checksum) = self.checksum {
+            bytes.extend_from_slice(&checksum.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Parse the header at the start of `bytes`.
+    pub fn parse(bytes: &[u8]) -> Result<Self, OodleError> {
+        let mut reader = ByteReader::new(bytes);
+
+        if reader.array::<4>()? != MAGIC {
+            return Err(OodleError::InvalidFormat("bad frame magic"));
+        }
+
+        if reader.u8()? != VERSION {
+            return Err(OodleError::InvalidFormat("unsupported frame version"));
+        }
+
+        let flags = reader.u8()?;
+        if flags & !(FLAG_SEEK_CHUNK_RESET | FLAG_QUANTUM_CRCS | FLAG_CHECKSUM) != 0 {
+            return Err(OodleError::InvalidFormat("unknown frame flags"));
+        }
+
+        let compressor = Compressor::try_from(reader.u8()? as oodle_sys::OodleLZ_Compressor)
+            .map_err(|_| OodleError::InvalidFormat("unknown frame compressor"))?;
+        let _reserved = reader.u8()?;
+        let decompressed_len = reader.u64()?;
+
+        let checksum = if flags & FLAG_CHECKSUM != 0 {
+            Some(reader.u32()?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            compressor,
+            decompressed_len,
+            seek_chunk_reset: flags & FLAG_SEEK_CHUNK_RESET != 0,
+            quantum_crcs: flags & FLAG_QUANTUM_CRCS != 0,
+            checksum,
+        })
+    }
+}
+
+/// Compress some data into a frame.
+///
+/// `options` default to [CompressOptions::for_compressor]. With `checksum`,
+/// the frame holds a CRC-32 of `decompressed`, which is checked by
+/// [decompress_framed].
+///
+/// # Example
+///
+/// ```rust
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// let framed = oodle_safe::frame::compress_framed(
+///     oodle_safe::Compressor::Kraken,
+///     decompressed,
+///     oodle_safe::CompressionLevel::Normal,
+///     None,
+///     true,
+/// )
+/// .unwrap_or_else(|err| panic!("compression failed: {}", err));
+///
+/// let output = oodle_safe::frame::decompress_framed(&framed)
+///     .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+///
+/// assert_eq!(output, decompressed);
+/// ```
+pub fn compress_framed(
+    compressor: Compressor,
+    decompressed: &[u8],
+    level: CompressionLevel,
+    options: Option<CompressOptions>,
+    checksum: bool,
+) -> Result<Vec<u8>, OodleError> {
+    let options = options.unwrap_or_else(|| CompressOptions::for_compressor(compressor, level));
+
+    let header = FrameHeader {
+        compressor,
+        decompressed_len: decompressed.len() as u64,
+        seek_chunk_reset: options.seek_chunk_reset,
+        quantum_crcs: options.send_quantum_crcs,
+        checksum: checksum.then(|| crc32(decompressed)),
+    };
+
+    let compressed = compress_to_vec(compressor, decompressed, level, Some(options), None, None)?;
+
+    let mut framed = header.to_bytes();
+    framed.extend_from_slice(&compressed);
+    Ok(framed)
+}
+
+/// Decompress a frame written by [compress_framed].
+///
+/// Returns [OodleError::CrcMismatch] if the decompressed data does not match
+/// the checksum of the frame.
+pub fn decompress_framed(framed: &[u8]) -> Result<Vec<u8>, OodleError> {
+    let header = FrameHeader::parse(framed)?;
+    let compressed = &framed[header.encoded_len()..];
+
+    let decompressed_len = usize::try_from(header.decompressed_len)
+        .map_err(|_| OodleError::InvalidFormat("frame length does not fit in memory"))?;
+
+    if decompressed_len > max_decompressed_len(compressed.len()) {
+        return Err(OodleError::InvalidFormat(
+            "frame length too large for its data",
+        ));
+    }
+
+    let check_crc = if header.quantum_crcs {
+        CheckCRC::Yes
+    } else {
+        CheckCRC::No
+    };
+
+    let mut decompressed = vec![0u8; decompressed_len];
+    let len = decompress(
+        compressed,
+        &mut decompressed,
+        None,
+        Some(check_crc),
+        None,
+        None,
+    )?;
+
+    if len != decompressed_len {
+        return Err(OodleError::CorruptData);
+    }
+
+    match header.checksum {
+        Some(checksum) if checksum != crc32(&decompressed) => Err(OodleError::CrcMismatch),
+        _ => Ok(decompressed),
+    }
+}
+
+/// Decompress data in the legacy layout, a little-endian u32 decompressed
+/// length followed by the compressed data.
+///
+/// # Example
+///
+/// ```rust
+/// let compressed = include_bytes!("../test_data/compressed");
+///
+/// let decompressed = oodle_safe::frame::decompress_legacy(compressed)
+///     .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+/// ```
+pub fn decompress_legacy(data: &[u8]) -> Result<Vec<u8>, OodleError> {
+    let mut reader = ByteReader::new(data);
+    let decompressed_len = reader.u32()? as usize;
+    let compressed = &data[4..];
+
+    if decompressed_len > max_decompressed_len(compressed.len()) {
+        return Err(OodleError::InvalidFormat(
+            "legacy length too large for its data",
+        ));
+    }
+
+    let mut decompressed = vec![0u8; decompressed_len];
+    let len = decompress(compressed, &mut decompressed, None, None, None, None)?;
+
+    if len != decompressed_len {
+        return Err(OodleError::CorruptData);
+    }
+
+    Ok(decompressed)
+}
+
+/// Read the u32 decompressed length of the legacy layout from `inner`, and
+/// return a reader decompressing the data following it.
+pub fn legacy_reader<R: Read>(mut inner: R) -> std::io::Result<DecompressReader<R>> {
+    let mut prefix = [0u8; 4];
+    inner.read_exact(&mut prefix)?;
+
+    let decompressed_len = u32::from_le_bytes(prefix);
+    Ok(DecompressReader::new(
+        inner,
+        decompressed_len as u64,
+        decompressed_len as usize,
+    )?)
+}
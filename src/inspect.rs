@@ -0,0 +1,187 @@
+use crate::{Compressor, OodleError, BLOCK_LEN};
+
+/// Information about a chunk of compressed data, which holds up to
+/// [BLOCK_LEN] bytes of decompressed data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkInfo {
+    /// Compressor used for the chunk.
+    pub compressor: Compressor,
+
+    /// Position of the chunk in the compressed data.
+    pub compressed_offset: usize,
+
+    /// Size of the chunk in the compressed data.
+    pub compressed_len: usize,
+
+    /// Size of the decompressed data of the chunk.
+    ///
+    /// The compressed data does not store the size of the last chunk, which is
+    /// reported as [BLOCK_LEN], an upper bound of its actual size.
+    pub decompressed_len: usize,
+
+    /// Whether the chunk can be decoded without the previous ones.
+    pub independent: bool,
+
+    /// Whether the chunk holds quantum CRCs, which can be checked with
+    /// [CheckCRC::Yes](crate::CheckCRC::Yes).
+    pub has_crcs: bool,
+}
+
+/// Information about compressed data, as reported by [inspect].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// Information about each chunk, in order.
+    pub chunks: Vec<ChunkInfo>,
+
+    /// Compressor used for all the chunks.
+    ///
+    /// This is [Compressor::Hydra] if different compressors of the Kraken
+    /// family were used, and `None` if other compressors were mixed.
+    pub compressor: Option<Compressor>,
+}
+
+impl StreamInfo {
+    /// Total size of the compressed data.
+    pub fn compressed_len(&self) -> usize {
+        self.chunks.iter().map(|x| x.compressed_len).sum()
+    }
+
+    /// Upper bound of the size of the decompressed data, which is within
+    /// [BLOCK_LEN] of the actual size.
+    pub fn max_decompressed_len(&self) -> usize {
+        self.chunks.iter().map(|x| x.decompressed_len).sum()
+    }
+
+    /// Whether every chunk can be decoded without the previous ones.
+    pub fn is_independent(&self) -> bool {
+        self.chunks.iter().all(|x| x.independent)
+    }
+
+    /// Whether every chunk holds quantum CRCs.
+    pub fn has_crcs(&self) -> bool {
+        self.chunks.iter().all(|x| x.has_crcs)
+    }
+}
+
+/// Walk the chunk headers of compressed data without decoding it.
+///
+/// Returns [OodleError::InvalidHeader] if the data does not start with a valid
+/// chunk, and [OodleError::CorruptData] if a later chunk is invalid or
+/// truncated.
+///
+/// # Example
+///
+/// ```rust
+/// let compressed = include_bytes!("../test_data/compressed");
+///
+/// let info = oodle_safe::inspect(&compressed[4..])
+///     .unwrap_or_else(|err| panic!("inspection failed: {}", err));
+///
+/// for chunk in &info.chunks {
+///     println!("{:?}: {} bytes", chunk.compressor, chunk.compressed_len);
+/// }
+/// ```
+pub fn inspect(compressed: &[u8]) -> Result<StreamInfo, OodleError> {
+    let mut chunks = Vec::new();
+    let mut compressed_pos = 0;
+    let mut decompressed_pos = 0;
+
+    while compressed_pos < compressed.len() {
+        let rest = &compressed[compressed_pos..];
+        let invalid = if chunks.is_empty() {
+            OodleError::InvalidHeader
+        } else {
+            OodleError::CorruptData
+        };
+
+        let mut independent = 0;
+        let compressor = unsafe {
+            oodle_sys::OodleLZ_GetChunkCompressor(
+                rest.as_ptr() as *const _,
+                rest.len() as isize,
+                &mut independent,
+            )
+        };
+        let compressor = Compressor::try_from(compressor).map_err(|_| invalid)?;
+
+        let mut end_pos = 0;
+        let step = unsafe {
+            oodle_sys::OodleLZ_GetCompressedStepForRawStep(
+                rest.as_ptr() as *const _,
+                rest.len() as isize,
+                decompressed_pos as isize,
+                BLOCK_LEN as isize,
+                &mut end_pos,
+                std::ptr::null_mut(),
+            )
+        };
+
+        if step <= 0 || step as usize > rest.len() || end_pos as usize <= decompressed_pos {
+            return Err(invalid);
+        }
+
+        chunks.push(ChunkInfo {
+            compressor,
+            compressed_offset: compressed_pos,
+            compressed_len: step as usize,
+            decompressed_len: end_pos as usize - decompressed_pos,
+            independent: independent != 0,
+            has_crcs: has_crcs(rest),
+        });
+
+        compressed_pos += step as usize;
+        decompressed_pos = end_pos as usize;
+    }
+
+    if chunks.is_empty() {
+        return Err(OodleError::InvalidHeader);
+    }
+
+    let compressor = unsafe {
+        oodle_sys::OodleLZ_GetAllChunksCompressor(
+            compressed.as_ptr() as *const _,
+            compressed.len() as isize,
+            decompressed_pos as isize,
+        )
+    };
+
+    // Oodle may not accept the decompressed size upper bound, in which case
+    // the chunk compressors are merged the same way.
+    let compressor = match compressor {
+        oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Invalid => merge_compressors(&chunks),
+        x => Compressor::try_from(x).ok(),
+    };
+
+    Ok(StreamInfo { chunks, compressor })
+}
+
+/// Whether the chunk starting `compressed` holds quantum CRCs.
+///
+/// Chunks of the Kraken family start with a two-byte header, whose low nibble
+/// is `0xC`, and whose second byte has its high bit set when CRCs are sent.
+fn has_crcs(compressed: &[u8]) -> bool {
+    matches!(compressed, [header, flags, ..] if header & 0x0F == 0x0C && flags & 0x80 != 0)
+}
+
+/// Compressor used for all the chunks, like `OodleLZ_GetAllChunksCompressor`.
+fn merge_compressors(chunks: &[ChunkInfo]) -> Option<Compressor> {
+    let is_new_lz = |x: Compressor| {
+        matches!(
+            x,
+            Compressor::Kraken
+                | Compressor::Leviathan
+                | Compressor::Mermaid
+                | Compressor::Selkie
+                | Compressor::Hydra
+        )
+    };
+
+    let first = chunks[0].compressor;
+    if chunks.iter().all(|x| x.compressor == first) {
+        Some(first)
+    } else if chunks.iter().all(|x| is_new_lz(x.compressor)) {
+        Some(Compressor::Hydra)
+    } else {
+        None
+    }
+}
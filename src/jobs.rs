@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once};
+
+/// Work handed over by Oodle to a [JobSystem].
+///
+/// The job must be run exactly once with [Job::run], after all its
+/// dependencies are done.
+pub struct Job {
+    function: oodle_sys::t_OodleFPVoidVoidStar,
+    data: *mut std::ffi::c_void,
+}
+
+// Oodle jobs are meant to run on any thread.
+unsafe impl Send for Job {}
+
+impl Job {
+    /// Run the job on the current thread.
+    pub fn run(self) {
+        if let Some(function) = self.function {
+            unsafe { function(self.data) };
+        }
+    }
+}
+
+/// Handle of a job started asynchronously by a [JobSystem].
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobHandle(NonZeroU64);
+
+impl JobHandle {
+    /// Create a handle from its raw nonzero value.
+    pub fn new(handle: NonZeroU64) -> Self {
+        Self(handle)
+    }
+
+    /// Raw value of the handle.
+    pub fn get(&self) -> NonZeroU64 {
+        self.0
+    }
+}
+
+/// Job system used by Oodle to run the internal jobs of the compressors.
+///
+/// Jobs are only used when [Jobify](crate::Jobify) is not
+/// [Jobify::Disable](crate::Jobify::Disable) and the compression options have
+/// a [JobSystemHandle]. Panicking in a job system aborts the process, as it
+/// is called from Oodle.
+pub trait JobSystem: Send + Sync {
+    /// Start `job` once all the `dependencies` are done.
+    ///
+    /// Returns the handle of the job, or `None` if it was run synchronously.
+    /// Jobs may start sub-jobs from within [Job::run].
+    fn run_job(&self, job: Job, dependencies: &[JobHandle]) -> Option<JobHandle>;
+
+    /// Wait until the job `handle` is done and release it.
+    ///
+    /// This is called exactly once for each handle returned by
+    /// [JobSystem::run_job], from the thread which called Oodle.
+    fn wait_job(&self, handle: JobHandle);
+
+    /// Number of jobs Oodle should try to keep in flight at once.
+    fn target_parallelism(&self) -> usize {
+        std::thread::available_parallelism().map_or(1, |x| x.get())
+    }
+}
+
+/// Typed handle to a [JobSystem], passed to the compressor with
+/// [CompressOptionsBuilder::job_system](crate::CompressOptionsBuilder::job_system).
+///
+/// Oodle only allows its job plugin to be installed once, so the first handle
+/// created installs it, with the [JobSystem::target_parallelism] of its job
+/// system. Every handle then routes the jobs to its own job system.
+///
+/// # Example
+///
+/// ```rust
+/// let job_system = oodle_safe::JobSystemHandle::new(oodle_safe::ThreadJobSystem::new());
+///
+/// let options = oodle_safe::CompressOptions::builder()
+///     .jobify(oodle_safe::Jobify::Normal)
+///     .job_system(job_system)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Copy)]
+pub struct JobSystemHandle {
+    system: &'static &'static dyn JobSystem,
+}
+
+impl JobSystemHandle {
+    /// Create a handle to `system`, installing the job plugin of Oodle if
+    /// needed.
+    ///
+    /// The job system is leaked, as the compression options using it may be
+    /// copied freely.
+    pub fn new(system: impl JobSystem + 'static) -> Self {
+        let system: &'static dyn JobSystem = Box::leak(Box::new(system));
+        let system: &'static &'static dyn JobSystem = Box::leak(Box::new(system));
+
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| unsafe {
+            oodle_sys::OodleCore_Plugins_SetJobSystemAndCount(
+                Some(run_job),
+                Some(wait_job),
+                system.target_parallelism() as i32,
+            )
+        });
+
+        Self { system }
+    }
+
+    /// Job system of the handle.
+    pub fn system(&self) -> &'static dyn JobSystem {
+        *self.system
+    }
+
+    /// User pointer passed by Oodle to the job plugin.
+    pub(crate) fn as_ptr(&self) -> *mut std::ffi::c_void {
+        self.system as *const &'static dyn JobSystem as *mut _
+    }
+}
+
+impl std::fmt::Debug for JobSystemHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("JobSystemHandle")
+            .field(&self.as_ptr())
+            .finish()
+    }
+}
+
+impl PartialEq for JobSystemHandle {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.system, other.system)
+    }
+}
+
+impl Eq for JobSystemHandle {}
+
+unsafe extern "C" fn run_job(
+    function: oodle_sys::t_OodleFPVoidVoidStar,
+    data: *mut std::ffi::c_void,
+    dependencies: *mut oodle_sys::OO_U64,
+    num_dependencies: std::ffi::c_int,
+    user_ptr: *mut std::ffi::c_void,
+) -> oodle_sys::OO_U64 {
+    let job = Job { function, data };
+
+    // Compressions without a job system run their jobs synchronously.
+    if user_ptr.is_null() {
+        job.run();
+        return 0;
+    }
+
+    let system = *(user_ptr as *const &'static dyn JobSystem);
+
+    // Dependencies are nonzero handles, which have the layout of JobHandle.
+    let dependencies = if num_dependencies > 0 {
+        std::slice::from_raw_parts(dependencies as *const JobHandle, num_dependencies as usize)
+    } else {
+        &[]
+    };
+
+    system
+        .run_job(job, dependencies)
+        .map_or(0, |x| x.get().get())
+}
+
+unsafe extern "C" fn wait_job(handle: oodle_sys::OO_U64, user_ptr: *mut std::ffi::c_void) {
+    if let (false, Some(handle)) = (user_ptr.is_null(), NonZeroU64::new(handle)) {
+        let system = *(user_ptr as *const &'static dyn JobSystem);
+        system.wait_job(JobHandle(handle));
+    }
+}
+
+/// Completion flag of a running job.
+#[derive(Default)]
+struct Completion {
+    done: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Completion {
+    fn finish(&self) {
+        *self.done.lock().unwrap() = true;
+        self.condvar.notify_all();
+    }
+
+    #[cfg(feature = "rayon")]
+    fn is_done(&self) -> bool {
+        *self.done.lock().unwrap()
+    }
+
+    fn wait(&self) {
+        let mut done = self.done.lock().unwrap();
+        while !*done {
+            done = self.condvar.wait(done).unwrap();
+        }
+    }
+}
+
+/// Running jobs of a job system, by handle.
+#[derive(Default)]
+struct JobTable {
+    last_handle: AtomicU64,
+    jobs: Mutex<HashMap<JobHandle, Arc<Completion>>>,
+}
+
+impl JobTable {
+    fn insert(&self) -> (JobHandle, Arc<Completion>) {
+        let handle = self.last_handle.fetch_add(1, Ordering::Relaxed) + 1;
+        let handle = JobHandle(NonZeroU64::new(handle).unwrap());
+        let completion = Arc::new(Completion::default());
+
+        self.jobs.lock().unwrap().insert(handle, completion.clone());
+        (handle, completion)
+    }
+
+    /// Completion flags of `handles`, which are still running or not waited.
+    fn get(&self, handles: &[JobHandle]) -> Vec<Arc<Completion>> {
+        let jobs = self.jobs.lock().unwrap();
+        handles
+            .iter()
+            .filter_map(|x| jobs.get(x).cloned())
+            .collect()
+    }
+
+    fn remove(&self, handle: JobHandle) -> Option<Arc<Completion>> {
+        self.jobs.lock().unwrap().remove(&handle)
+    }
+}
+
+/// [JobSystem] running each job on its own thread.
+#[derive(Default)]
+pub struct ThreadJobSystem {
+    jobs: JobTable,
+}
+
+impl ThreadJobSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl JobSystem for ThreadJobSystem {
+    fn run_job(&self, job: Job, dependencies: &[JobHandle]) -> Option<JobHandle> {
+        let dependencies = self.jobs.get(dependencies);
+        let (handle, completion) = self.jobs.insert();
+
+        std::thread::spawn(move || {
+            dependencies.iter().for_each(|x| x.wait());
+            job.run();
+            completion.finish();
+        });
+
+        Some(handle)
+    }
+
+    fn wait_job(&self, handle: JobHandle) {
+        if let Some(completion) = self.jobs.remove(handle) {
+            completion.wait();
+        }
+    }
+}
+
+/// [JobSystem] running the jobs on the global rayon thread pool.
+#[cfg(feature = "rayon")]
+#[derive(Default)]
+pub struct RayonJobSystem {
+    jobs: JobTable,
+}
+
+#[cfg(feature = "rayon")]
+impl RayonJobSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for `completion`, running other pool work in the meantime when
+    /// called from the pool, so that waiting jobs cannot starve it.
+    fn wait(completion: &Completion) {
+        while !completion.is_done() {
+            match rayon::yield_now() {
+                Some(rayon::Yield::Executed) => {}
+                Some(rayon::Yield::Idle) => std::thread::yield_now(),
+                None => return completion.wait(),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl JobSystem for RayonJobSystem {
+    fn run_job(&self, job: Job, dependencies: &[JobHandle]) -> Option<JobHandle> {
+        let dependencies = self.jobs.get(dependencies);
+        let (handle, completion) = self.jobs.insert();
+
+        rayon::spawn(move || {
+            dependencies.iter().for_each(|x| Self::wait(x));
+            job.run();
+            completion.finish();
+        });
+
+        Some(handle)
+    }
+
+    fn wait_job(&self, handle: JobHandle) {
+        if let Some(completion) = self.jobs.remove(handle) {
+            Self::wait(&completion);
+        }
+    }
+
+    fn target_parallelism(&self) -> usize {
+        rayon::current_num_threads()
+    }
+}
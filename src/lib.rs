@@ -9,13 +9,57 @@
 #[cfg(test)]
 mod tests;
 
+mod alloc;
+pub mod archive;
+mod bytes;
+mod callback;
+pub mod container;
+mod crc32;
+mod decoder;
+mod encoder;
+mod error;
+pub mod frame;
+mod inspect;
+mod jobs;
+#[cfg(any(feature = "log", feature = "tracing"))]
+mod logging;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod seek;
+mod stream;
+mod threaded;
+#[cfg(feature = "unreal")]
+pub mod unreal;
+
 use oodle_sys;
 
+use callback::{decompress_callback, CallbackData};
+
+pub use alloc::{
+    set_allocator, AllocationStats, CountingAllocator, GlobalAllocator, OodleAllocator,
+};
+pub use callback::{CallbackAction, DecompressProgress};
+pub use decoder::Decoder;
+pub use encoder::{compress_scratch_mem_bound, Encoder};
+pub use error::OodleError;
+pub use inspect::{inspect, ChunkInfo, StreamInfo};
+#[cfg(feature = "rayon")]
+pub use jobs::RayonJobSystem;
+pub use jobs::{Job, JobHandle, JobSystem, JobSystemHandle, ThreadJobSystem};
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub use logging::{install_logger, take_assertion, Assertion, AssertionAction};
+#[cfg(feature = "rayon")]
+pub use parallel::{compress_parallel, decompress_parallel};
+pub use seek::{compress_with_seek_table, SeekTable};
+pub use stream::{CompressWriter, DecompressReader, SeekableReader};
+pub use threaded::decompress_threaded;
+
 include!("constants.rs");
 
 /// Set of compression algorithms.
 ///
 /// Each compressor has its own trade-offs between compression ratio and speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Compressor {
     /// No compression, just a copy
     None,
@@ -49,6 +93,55 @@ impl Into<oodle_sys::OodleLZ_Compressor> for Compressor {
     }
 }
 
+impl TryFrom<oodle_sys::OodleLZ_Compressor> for Compressor {
+    type Error = OodleError;
+
+    fn try_from(compressor: oodle_sys::OodleLZ_Compressor) -> Result<Self, OodleError> {
+        match compressor {
+            oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_None => Ok(Compressor::None),
+            oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Kraken => Ok(Compressor::Kraken),
+            oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Leviathan => Ok(Compressor::Leviathan),
+            oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Mermaid => Ok(Compressor::Mermaid),
+            oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Selkie => Ok(Compressor::Selkie),
+            oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Hydra => Ok(Compressor::Hydra),
+            _ => Err(OodleError::UnknownValue("compressor")),
+        }
+    }
+}
+
+impl Compressor {
+    /// Iterate over all the compressors.
+    pub fn all() -> impl Iterator<Item = Self> {
+        [
+            Compressor::None,
+            Compressor::Kraken,
+            Compressor::Leviathan,
+            Compressor::Mermaid,
+            Compressor::Selkie,
+            Compressor::Hydra,
+        ]
+        .into_iter()
+    }
+}
+
+/// Name given by `OodleLZ_Compressor_GetName`.
+impl std::fmt::Display for Compressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(oodle_name(unsafe {
+            oodle_sys::OodleLZ_Compressor_GetName((*self).into())
+        }))
+    }
+}
+
+/// Parse the name given by [Display](std::fmt::Display), ignoring case.
+impl std::str::FromStr for Compressor {
+    type Err = OodleError;
+
+    fn from_str(name: &str) -> Result<Self, OodleError> {
+        parse_name(Compressor::all(), name, "compressor")
+    }
+}
+
 /// Set of compression levels.
 ///
 /// A compressed data stream can be decompressed with any level, but the
@@ -58,6 +151,10 @@ impl Into<oodle_sys::OodleLZ_Compressor> for Compressor {
 /// find the best compressed bitstream. It does not directly impact
 /// decompression speed, it trades off encode speed for compression bitstream
 /// quality.
+///
+/// Levels are compared by their numeric value, from [CompressionLevel::Min] to
+/// [CompressionLevel::Max], so aliases are equal to the level they stand for.
+#[derive(Debug, Clone, Copy)]
 pub enum CompressionLevel {
     /// Don't compress, just copy the data
     None,
@@ -146,8 +243,118 @@ impl Default for CompressionLevel {
     }
 }
 
+#[rustfmt::skip]
+impl TryFrom<oodle_sys::OodleLZ_CompressionLevel> for CompressionLevel {
+    type Error = OodleError;
+
+    fn try_from(level: oodle_sys::OodleLZ_CompressionLevel) -> Result<Self, OodleError> {
+        match level {
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_None => Ok(CompressionLevel::None),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_SuperFast => Ok(CompressionLevel::SuperFast),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_VeryFast => Ok(CompressionLevel::VeryFast),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_Fast => Ok(CompressionLevel::Fast),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_Normal => Ok(CompressionLevel::Normal),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_Optimal1 => Ok(CompressionLevel::Optimal1),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_Optimal2 => Ok(CompressionLevel::Optimal2),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_Optimal3 => Ok(CompressionLevel::Optimal3),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_Optimal4 => Ok(CompressionLevel::Optimal4),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_Optimal5 => Ok(CompressionLevel::Optimal5),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_HyperFast1 => Ok(CompressionLevel::HyperFast1),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_HyperFast2 => Ok(CompressionLevel::HyperFast2),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_HyperFast3 => Ok(CompressionLevel::HyperFast3),
+            oodle_sys::OodleLZ_CompressionLevel_OodleLZ_CompressionLevel_HyperFast4 => Ok(CompressionLevel::HyperFast4),
+            _ => Err(OodleError::UnknownValue("compression level")),
+        }
+    }
+}
+
+impl CompressionLevel {
+    /// Iterate over all the compression levels, from the fastest to the
+    /// slowest, without the aliases.
+    pub fn all() -> impl Iterator<Item = Self> {
+        [
+            CompressionLevel::HyperFast4,
+            CompressionLevel::HyperFast3,
+            CompressionLevel::HyperFast2,
+            CompressionLevel::HyperFast1,
+            CompressionLevel::None,
+            CompressionLevel::SuperFast,
+            CompressionLevel::VeryFast,
+            CompressionLevel::Fast,
+            CompressionLevel::Normal,
+            CompressionLevel::Optimal1,
+            CompressionLevel::Optimal2,
+            CompressionLevel::Optimal3,
+            CompressionLevel::Optimal4,
+            CompressionLevel::Optimal5,
+        ]
+        .into_iter()
+    }
+
+    /// Numeric value of the level.
+    fn value(self) -> oodle_sys::OodleLZ_CompressionLevel {
+        self.into()
+    }
+}
+
+impl PartialEq for CompressionLevel {
+    fn eq(&self, other: &Self) -> bool {
+        self.value() == other.value()
+    }
+}
+
+impl Eq for CompressionLevel {}
+
+impl std::hash::Hash for CompressionLevel {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.value().hash(state)
+    }
+}
+
+impl PartialOrd for CompressionLevel {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CompressionLevel {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.value().cmp(&other.value())
+    }
+}
+
+/// Name given by `OodleLZ_CompressionLevel_GetName`, which is the name of the
+/// level an alias stands for.
+impl std::fmt::Display for CompressionLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(oodle_name(unsafe {
+            oodle_sys::OodleLZ_CompressionLevel_GetName(self.value())
+        }))
+    }
+}
+
+/// Parse the name given by [Display](std::fmt::Display) or the name of an
+/// alias, ignoring case.
+impl std::str::FromStr for CompressionLevel {
+    type Err = OodleError;
+
+    fn from_str(name: &str) -> Result<Self, OodleError> {
+        let aliases = [
+            ("Optimal", CompressionLevel::Optimal),
+            ("HyperFast", CompressionLevel::HyperFast),
+            ("Max", CompressionLevel::Max),
+            ("Min", CompressionLevel::Min),
+        ];
+
+        match aliases.iter().find(|(x, _)| x.eq_ignore_ascii_case(name)) {
+            Some((_, level)) => Ok(*level),
+            None => parse_name(CompressionLevel::all(), name, "compression level"),
+        }
+    }
+}
+
 /// Decoder profile to target.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Profile {
     /// Main profile, full feature set
     Main,
@@ -165,18 +372,45 @@ impl Into<oodle_sys::OodleLZ_Profile> for Profile {
     }
 }
 
-impl From<oodle_sys::OodleLZ_Profile> for Profile {
-    fn from(profile: oodle_sys::OodleLZ_Profile) -> Self {
+impl TryFrom<oodle_sys::OodleLZ_Profile> for Profile {
+    type Error = OodleError;
+
+    fn try_from(profile: oodle_sys::OodleLZ_Profile) -> Result<Self, OodleError> {
         match profile {
-            oodle_sys::OodleLZ_Profile_OodleLZ_Profile_Main => Profile::Main,
-            oodle_sys::OodleLZ_Profile_OodleLZ_Profile_Reduced => Profile::Reduced,
-            _ => panic!("Invalid profile"),
+            oodle_sys::OodleLZ_Profile_OodleLZ_Profile_Main => Ok(Profile::Main),
+            oodle_sys::OodleLZ_Profile_OodleLZ_Profile_Reduced => Ok(Profile::Reduced),
+            _ => Err(OodleError::UnknownValue("profile")),
         }
     }
 }
 
+impl Profile {
+    /// Iterate over all the decoder profiles.
+    pub fn all() -> impl Iterator<Item = Self> {
+        [Profile::Main, Profile::Reduced].into_iter()
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Profile::Main => "Main",
+            Profile::Reduced => "Reduced",
+        })
+    }
+}
+
+/// Parse the name given by [Display](std::fmt::Display), ignoring case.
+impl std::str::FromStr for Profile {
+    type Err = OodleError;
+
+    fn from_str(name: &str) -> Result<Self, OodleError> {
+        parse_name(Profile::all(), name, "profile")
+    }
+}
+
 /// Controls the amount of internal threading used by the compressor.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Jobify {
     /// Use compressor default for level of internal job usage
     Default,
@@ -202,25 +436,59 @@ impl Into<oodle_sys::OodleLZ_Jobify> for Jobify {
     }
 }
 
-impl From<oodle_sys::OodleLZ_Jobify> for Jobify {
-    fn from(jobify: oodle_sys::OodleLZ_Jobify) -> Self {
+impl TryFrom<oodle_sys::OodleLZ_Jobify> for Jobify {
+    type Error = OodleError;
+
+    fn try_from(jobify: oodle_sys::OodleLZ_Jobify) -> Result<Self, OodleError> {
         match jobify {
-            oodle_sys::OodleLZ_Jobify_OodleLZ_Jobify_Default => Jobify::Default,
-            oodle_sys::OodleLZ_Jobify_OodleLZ_Jobify_Disable => Jobify::Disable,
-            oodle_sys::OodleLZ_Jobify_OodleLZ_Jobify_Normal => Jobify::Normal,
-            oodle_sys::OodleLZ_Jobify_OodleLZ_Jobify_Aggressive => Jobify::Aggressive,
-            _ => panic!("Invalid jobify"),
+            oodle_sys::OodleLZ_Jobify_OodleLZ_Jobify_Default => Ok(Jobify::Default),
+            oodle_sys::OodleLZ_Jobify_OodleLZ_Jobify_Disable => Ok(Jobify::Disable),
+            oodle_sys::OodleLZ_Jobify_OodleLZ_Jobify_Normal => Ok(Jobify::Normal),
+            oodle_sys::OodleLZ_Jobify_OodleLZ_Jobify_Aggressive => Ok(Jobify::Aggressive),
+            _ => Err(OodleError::UnknownValue("jobify")),
         }
     }
 }
 
+impl Jobify {
+    /// Iterate over all the job usages.
+    pub fn all() -> impl Iterator<Item = Self> {
+        [
+            Jobify::Default,
+            Jobify::Disable,
+            Jobify::Normal,
+            Jobify::Aggressive,
+        ]
+        .into_iter()
+    }
+}
+
+/// Name given by `OodleLZ_Jobify_GetName`.
+impl std::fmt::Display for Jobify {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(oodle_name(unsafe {
+            oodle_sys::OodleLZ_Jobify_GetName((*self).into())
+        }))
+    }
+}
+
+/// Parse the name given by [Display](std::fmt::Display), ignoring case.
+impl std::str::FromStr for Jobify {
+    type Err = OodleError;
+
+    fn from_str(name: &str) -> Result<Self, OodleError> {
+        parse_name(Jobify::all(), name, "jobify")
+    }
+}
+
 /// Options to use for compression.
 ///
 /// Typically, you would use the default options and only change the fields you
 /// need to modify.
 ///
-/// To ensure that the options are valid, call [CompressOptions::validate]
-/// after modifying the fields.
+/// Use [CompressOptions::builder] to modify the fields, which checks that the
+/// options are valid. [CompressOptions::validate] can also be used to let
+/// Oodle fix invalid values.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CompressOptions {
     /// Was previously named `verbosity`, set to 0
@@ -274,8 +542,9 @@ pub struct CompressOptions {
     /// Controls internal job usage for the compressor.
     jobify: Jobify,
 
-    /// User pointer passed through to RunJob and WaitJob callbacks.
-    jobify_user_ptr: *mut std::ffi::c_void,
+    /// Job system running the internal jobs, passed to the job plugin as its
+    /// user pointer.
+    job_system: Option<JobSystemHandle>,
 
     /// Far match must be at least this long.
     far_match_min_len: i32,
@@ -291,7 +560,300 @@ impl CompressOptions {
     pub fn validate(&mut self) {
         let options: *mut oodle_sys::OodleLZ_CompressOptions = &mut (*self).into();
         unsafe { oodle_sys::OodleLZ_CompressOptions_Validate(options) };
-        *self = CompressOptions::from(unsafe { *options });
+        *self = CompressOptions {
+            job_system: self.job_system,
+            ..CompressOptions::try_from(unsafe { *options })
+                .expect("Oodle validated options out of range")
+        };
+    }
+
+    /// Default options of Oodle for the given compressor and level.
+    ///
+    /// These are the options used by [compress] when no options are given.
+    /// Use [CompressOptionsBuilder::from] to modify them.
+    pub fn for_compressor(compressor: Compressor, level: CompressionLevel) -> Self {
+        let options = unsafe {
+            *oodle_sys::OodleLZ_CompressOptions_GetDefault(compressor.into(), level.into())
+        };
+
+        options
+            .try_into()
+            .expect("Oodle default options out of range")
+    }
+
+    /// Create a builder starting from the default options.
+    pub fn builder() -> CompressOptionsBuilder {
+        CompressOptionsBuilder::new()
+    }
+
+    /// Minimum match length.
+    pub fn min_match_len(&self) -> i32 {
+        self.min_match_len
+    }
+
+    /// Whether chunks are independent, for seeking and parallelism.
+    pub fn seek_chunk_reset(&self) -> bool {
+        self.seek_chunk_reset
+    }
+
+    /// Length of independent seek chunks.
+    pub fn seek_chunk_len(&self) -> u32 {
+        self.seek_chunk_len
+    }
+
+    /// Decoder profile to target.
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
+
+    /// Maximum offset for matches, <= 0 means infinite.
+    pub fn dictionary_size(&self) -> i32 {
+        self.dictionary_size
+    }
+
+    /// Bytes that must be gained to accept a speed-decreasing decision.
+    pub fn space_speed_tradeoff_bytes(&self) -> i32 {
+        self.space_speed_tradeoff_bytes
+    }
+
+    /// Whether the encoder sends CRCs for each compressed quantum.
+    pub fn send_quantum_crcs(&self) -> bool {
+        self.send_quantum_crcs
+    }
+
+    /// Size of local dictionary before needing a long range matcher.
+    pub fn max_local_dictionary_size(&self) -> u32 {
+        self.max_local_dictionary_size
+    }
+
+    /// Whether the encoder finds matches beyond the local dictionary.
+    pub fn make_long_range_matcher(&self) -> bool {
+        self.make_long_range_matcher
+    }
+
+    /// Log2 of the size of the match finder structure, 0 for default.
+    pub fn match_table_size_log2(&self) -> i32 {
+        self.match_table_size_log2
+    }
+
+    /// Internal job usage for the compressor.
+    pub fn jobify(&self) -> Jobify {
+        self.jobify
+    }
+
+    /// Job system running the internal jobs of the compressor.
+    pub fn job_system(&self) -> Option<JobSystemHandle> {
+        self.job_system
+    }
+
+    /// Minimum length of far matches.
+    pub fn far_match_min_len(&self) -> i32 {
+        self.far_match_min_len
+    }
+
+    /// Log2 of the offset that must meet the far match minimum length.
+    pub fn far_match_offset_log2(&self) -> i32 {
+        self.far_match_offset_log2
+    }
+
+    /// Check the documented constraints of the options, and that
+    /// [CompressOptions::validate] would leave them unchanged, apart from zero
+    /// fields which Oodle replaces with their default value.
+    pub(crate) fn check(&self) -> Result<(), OodleError> {
+        if self.seek_chunk_len != 0
+            && (!self.seek_chunk_len.is_power_of_two() || self.seek_chunk_len < BLOCK_LEN)
+        {
+            return Err(OodleError::InvalidOptions(
+                "seek_chunk_len must be a power of 2 and >= BLOCK_LEN",
+            ));
+        }
+
+        if self.max_local_dictionary_size != 0
+            && (!self.max_local_dictionary_size.is_power_of_two()
+                || self.max_local_dictionary_size >= LOCALDICTIONARYSIZE_MAX)
+        {
+            return Err(OodleError::InvalidOptions(
+                "max_local_dictionary_size must be a power of 2 and < LOCALDICTIONARYSIZE_MAX",
+            ));
+        }
+
+        if self.min_match_len < 0
+            || self.space_speed_tradeoff_bytes < 0
+            || self.match_table_size_log2 < 0
+            || self.far_match_min_len < 0
+            || self.far_match_offset_log2 < 0
+        {
+            return Err(OodleError::InvalidOptions("negative value"));
+        }
+
+        let mut validated = *self;
+        validated.validate();
+
+        fn same<T: PartialEq + Default>(value: T, validated: T) -> bool {
+            value == T::default() || value == validated
+        }
+
+        if same(self.min_match_len, validated.min_match_len)
+            && same(self.seek_chunk_len, validated.seek_chunk_len)
+            && (self.dictionary_size <= 0 || self.dictionary_size == validated.dictionary_size)
+            && same(
+                self.space_speed_tradeoff_bytes,
+                validated.space_speed_tradeoff_bytes,
+            )
+            && same(
+                self.max_local_dictionary_size,
+                validated.max_local_dictionary_size,
+            )
+            && same(self.match_table_size_log2, validated.match_table_size_log2)
+            && same(self.far_match_min_len, validated.far_match_min_len)
+            && same(self.far_match_offset_log2, validated.far_match_offset_log2)
+        {
+            Ok(())
+        } else {
+            Err(OodleError::InvalidOptions("rejected by Oodle validation"))
+        }
+    }
+}
+
+/// Builder for [CompressOptions].
+///
+/// Unset fields keep their default value. The documented constraints of the
+/// options are checked by [CompressOptionsBuilder::build], instead of letting
+/// Oodle silently rewrite invalid values.
+///
+/// # Example
+///
+/// ```rust
+/// let options = oodle_safe::CompressOptions::builder()
+///     .seek_chunk_reset(true)
+///     .seek_chunk_len(oodle_safe::BLOCK_LEN * 2)
+///     .send_quantum_crcs(true)
+///     .build()
+///     .unwrap_or_else(|err| panic!("invalid options: {}", err));
+///
+/// assert!(options.seek_chunk_reset());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressOptionsBuilder {
+    options: CompressOptions,
+}
+
+impl CompressOptionsBuilder {
+    /// Create a builder starting from the default options.
+    pub fn new() -> Self {
+        Self::from(CompressOptions::default())
+    }
+
+    /// Cannot be used to reduce a compressor's default MML, but can be higher.
+    pub fn min_match_len(mut self, min_match_len: i32) -> Self {
+        self.options.min_match_len = min_match_len;
+        self
+    }
+
+    /// Whether chunks should be independent, for seeking and parallelism.
+    pub fn seek_chunk_reset(mut self, seek_chunk_reset: bool) -> Self {
+        self.options.seek_chunk_reset = seek_chunk_reset;
+        self
+    }
+
+    /// Length of independent seek chunks. Must be a power of 2 and >=
+    /// [BLOCK_LEN].
+    pub fn seek_chunk_len(mut self, seek_chunk_len: u32) -> Self {
+        self.options.seek_chunk_len = seek_chunk_len;
+        self
+    }
+
+    /// Decoder profile to target.
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.options.profile = profile;
+        self
+    }
+
+    /// Maximum offset for matches, <= 0 means infinite.
+    pub fn dictionary_size(mut self, dictionary_size: i32) -> Self {
+        self.options.dictionary_size = dictionary_size;
+        self
+    }
+
+    /// Number of bytes it must gain to accept a speed-decreasing decision.
+    pub fn space_speed_tradeoff_bytes(mut self, space_speed_tradeoff_bytes: i32) -> Self {
+        self.options.space_speed_tradeoff_bytes = space_speed_tradeoff_bytes;
+        self
+    }
+
+    /// Whether the encoder should send CRCs for each compressed quantum.
+    pub fn send_quantum_crcs(mut self, send_quantum_crcs: bool) -> Self {
+        self.options.send_quantum_crcs = send_quantum_crcs;
+        self
+    }
+
+    /// Size of local dictionary before needing a long range matcher. Must be
+    /// a power of 2 and < [LOCALDICTIONARYSIZE_MAX].
+    pub fn max_local_dictionary_size(mut self, max_local_dictionary_size: u32) -> Self {
+        self.options.max_local_dictionary_size = max_local_dictionary_size;
+        self
+    }
+
+    /// Whether the encoder should find matches beyond the local dictionary.
+    ///
+    /// The long range matcher is built by the encoder on each call to
+    /// [compress], as prebuilt matchers require Oodle Ext.
+    pub fn make_long_range_matcher(mut self, make_long_range_matcher: bool) -> Self {
+        self.options.make_long_range_matcher = make_long_range_matcher;
+        self
+    }
+
+    /// Log2 of the size of the match finder structure, 0 for default.
+    pub fn match_table_size_log2(mut self, match_table_size_log2: i32) -> Self {
+        self.options.match_table_size_log2 = match_table_size_log2;
+        self
+    }
+
+    /// Internal job usage for the compressor.
+    pub fn jobify(mut self, jobify: Jobify) -> Self {
+        self.options.jobify = jobify;
+        self
+    }
+
+    /// Job system running the internal jobs of the compressor.
+    ///
+    /// Without a job system, the jobs run synchronously on the calling thread.
+    pub fn job_system(mut self, job_system: JobSystemHandle) -> Self {
+        self.options.job_system = Some(job_system);
+        self
+    }
+
+    /// Far match must be at least this long.
+    pub fn far_match_min_len(mut self, far_match_min_len: i32) -> Self {
+        self.options.far_match_min_len = far_match_min_len;
+        self
+    }
+
+    /// Log2 of the offset that must meet the far match minimum length.
+    pub fn far_match_offset_log2(mut self, far_match_offset_log2: i32) -> Self {
+        self.options.far_match_offset_log2 = far_match_offset_log2;
+        self
+    }
+
+    /// Check the options and build them.
+    ///
+    /// Returns [OodleError::InvalidOptions] if a field breaks its documented
+    /// constraints, or would be rewritten by [CompressOptions::validate].
+    pub fn build(self) -> Result<CompressOptions, OodleError> {
+        self.options.check()?;
+        Ok(self.options)
+    }
+}
+
+impl Default for CompressOptionsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<CompressOptions> for CompressOptionsBuilder {
+    fn from(options: CompressOptions) -> Self {
+        Self { options }
     }
 }
 
@@ -311,7 +873,10 @@ impl Into<oodle_sys::OodleLZ_CompressOptions> for CompressOptions {
             makeLongRangeMatcher: if self.make_long_range_matcher { 1 } else { 0 },
             matchTableSizeLog2: self.match_table_size_log2,
             jobify: self.jobify.into(),
-            jobifyUserPtr: self.jobify_user_ptr,
+            jobifyUserPtr: match self.job_system {
+                Some(x) => x.as_ptr(),
+                None => std::ptr::null_mut(),
+            },
             farMatchMinLen: self.far_match_min_len,
             farMatchOffsetLog2: self.far_match_offset_log2,
             reserved: self.reserved,
@@ -319,14 +884,18 @@ impl Into<oodle_sys::OodleLZ_CompressOptions> for CompressOptions {
     }
 }
 
-impl From<oodle_sys::OodleLZ_CompressOptions> for CompressOptions {
-    fn from(options: oodle_sys::OodleLZ_CompressOptions) -> Self {
-        Self {
+/// The job system cannot be recovered from the raw user pointer, and is left
+/// unset.
+impl TryFrom<oodle_sys::OodleLZ_CompressOptions> for CompressOptions {
+    type Error = OodleError;
+
+    fn try_from(options: oodle_sys::OodleLZ_CompressOptions) -> Result<Self, OodleError> {
+        Ok(Self {
             unused: options.unused_was_verbosity,
             min_match_len: options.minMatchLen,
             seek_chunk_reset: options.seekChunkReset != 0,
             seek_chunk_len: options.seekChunkLen as u32,
-            profile: options.profile.into(),
+            profile: options.profile.try_into()?,
             dictionary_size: options.dictionarySize,
             space_speed_tradeoff_bytes: options.spaceSpeedTradeoffBytes,
             unused2: options.unused_was_maxHuffmansPerChunk,
@@ -334,25 +903,57 @@ impl From<oodle_sys::OodleLZ_CompressOptions> for CompressOptions {
             max_local_dictionary_size: options.maxLocalDictionarySize as u32,
             make_long_range_matcher: options.makeLongRangeMatcher != 0,
             match_table_size_log2: options.matchTableSizeLog2,
-            jobify: options.jobify.into(),
-            jobify_user_ptr: options.jobifyUserPtr,
+            jobify: options.jobify.try_into()?,
+            job_system: None,
             far_match_min_len: options.farMatchMinLen,
             far_match_offset_log2: options.farMatchOffsetLog2,
             reserved: options.reserved,
-        }
+        })
     }
 }
 
+/// Default options that are not specific to a compressor or level.
+///
+/// Use [CompressOptions::for_compressor] to get the options that Oodle
+/// actually applies for a given compressor and level.
 impl Default for CompressOptions {
     fn default() -> Self {
-        let options = unsafe {
-            *oodle_sys::OodleLZ_CompressOptions_GetDefault(
-                Compressor::None.into(),
-                CompressionLevel::None.into(),
-            )
-        };
+        CompressOptions::for_compressor(Compressor::None, CompressionLevel::None)
+    }
+}
 
-        options.into()
+/// Static name returned by one of the `*_GetName` functions of Oodle.
+fn oodle_name(name: *const std::ffi::c_char) -> &'static str {
+    if name.is_null() {
+        return "";
+    }
+
+    unsafe { std::ffi::CStr::from_ptr(name) }
+        .to_str()
+        .unwrap_or("")
+}
+
+/// Find the value of `values` displayed as `name`, ignoring case.
+fn parse_name<T: std::fmt::Display>(
+    mut values: impl Iterator<Item = T>,
+    name: &str,
+    kind: &'static str,
+) -> Result<T, OodleError> {
+    values
+        .find(|x| x.to_string().eq_ignore_ascii_case(name.trim()))
+        .ok_or(OodleError::UnknownValue(kind))
+}
+
+/// Size of the buffer needed to hold the compressed data of `decompressed_len`
+/// bytes in the worst case.
+///
+/// The compressed buffer passed to [compress] must be at least this large.
+pub fn compressed_buffer_size_needed(compressor: Compressor, decompressed_len: usize) -> usize {
+    unsafe {
+        oodle_sys::OodleLZ_GetCompressedBufferSizeNeeded(
+            compressor.into(),
+            decompressed_len as isize,
+        ) as usize
     }
 }
 
@@ -369,20 +970,39 @@ impl Default for CompressOptions {
 /// * `scratch_memory` - Scratch memory to use for compression.
 ///
 /// When setting optionnal parameters to `None`, the default value will be used.
+/// The default `options` are [CompressOptions::for_compressor] of `compressor`
+/// and `level`.
 ///
 /// # Returns
 ///
 /// The size of the compressed data.
 ///
+/// # Errors
+///
+/// * [OodleError::BufferTooSmall] if `compressed` is smaller than the worst
+///   case compressed size of `decompressed`.
+/// * [OodleError::InvalidOptions] if `options` break their documented
+///   constraints.
+/// * [OodleError::InvalidDictionary] if `dictionary_base` does not directly
+///   precede `decompressed` in memory, or its length is not a multiple of
+///   [BLOCK_LEN].
+/// * [OodleError::CompressionFailed] if the compressor failed.
+///
 /// # Example
 ///
 /// ```rust
 /// // Load decompressed data from a file (or any other source).
 /// let decompressed = include_bytes!("../test_data/decompressed");
 ///
-/// // Allocate compressed buffer with some extra space in case the compression
-/// // adds some overhead like the OodleLZ block header.
-/// let mut compressed = vec![0u8; decompressed.len() + 8];
+/// // Allocate compressed buffer with enough room for the worst case, as the
+/// // compression adds some overhead like the OodleLZ block headers.
+/// let mut compressed = vec![
+///     0u8;
+///     oodle_safe::compressed_buffer_size_needed(
+///         oodle_safe::Compressor::Kraken,
+///         decompressed.len(),
+///     )
+/// ];
 ///
 /// // Compress the data.
 /// let compressed_size = oodle_safe::compress(
@@ -390,11 +1010,14 @@ impl Default for CompressOptions {
 ///     decompressed,
 ///     &mut compressed,
 ///     oodle_safe::CompressionLevel::Normal, // same as default
-///     Some(oodle_safe::CompressOptions::default()), // same as default
+///     Some(oodle_safe::CompressOptions::for_compressor(
+///         oodle_safe::Compressor::Kraken,
+///         oodle_safe::CompressionLevel::Normal,
+///     )), // same as default
 ///     None,
 ///     None,
 /// )
-/// .unwrap_or_else(|_| panic!("compression failed"));
+/// .unwrap_or_else(|err| panic!("compression failed: {}", err));
 ///
 /// // Trim the output buffer to the actual size of the compressed data.
 /// let compressed = &compressed[..compressed_size];
@@ -407,14 +1030,35 @@ pub fn compress(
     options: Option<CompressOptions>,
     dictionary_base: Option<&[u8]>,
     scratch_memory: Option<&mut [u8]>,
-) -> Result<usize, u32> {
+) -> Result<usize, OodleError> {
+    if decompressed.is_empty() {
+        return Ok(0);
+    }
+
+    let required = compressed_buffer_size_needed(compressor, decompressed.len());
+
+    if compressed.len() < required {
+        return Err(OodleError::BufferTooSmall {
+            required,
+            provided: compressed.len(),
+        });
+    }
+
     let options = match options {
-        Some(x) => &x.into(),
-        None => std::ptr::null() as *const _,
+        Some(x) => {
+            x.check()?;
+            x
+        }
+        None => CompressOptions::for_compressor(compressor, level),
     };
 
+    let options: oodle_sys::OodleLZ_CompressOptions = options.into();
+
     let dictionary_base = match dictionary_base {
-        Some(x) => x.as_ptr(),
+        Some(x) => {
+            check_dictionary(x, decompressed.as_ptr())?;
+            x.as_ptr()
+        }
         None => std::ptr::null(),
     };
 
@@ -423,6 +1067,9 @@ pub fn compress(
         None => (std::ptr::null_mut(), 0),
     };
 
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    logging::clear_assertion();
+
     let result = unsafe {
         oodle_sys::OodleLZ_Compress(
             compressor.into(),
@@ -430,16 +1077,23 @@ pub fn compress(
             decompressed.len() as isize,
             compressed.as_mut_ptr() as *mut _,
             level.into(),
-            options,
+            &options,
             dictionary_base as *const _,
-            std::ptr::null(), // TODO: add long_range_matcher
+            // Long range matchers can only be created with Oodle Ext, which is
+            // not part of the core library bound by oodle-sys. With
+            // `make_long_range_matcher`, the encoder builds its own for each
+            // call instead.
+            std::ptr::null(),
             scratch_memory as *mut _,
             scratch_memory_len,
         ) as usize
     };
 
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    logging::check_assertion()?;
+
     if result == FAILED as usize {
-        Err(FAILED)
+        Err(OodleError::CompressionFailed)
     } else {
         // This is necessary to avoid double free-ing the buffer when slicing
         // the compressed buffer after the compression.
@@ -449,10 +1103,56 @@ pub fn compress(
     }
 }
 
+/// Compress some data from memory to a newly allocated vector.
+///
+/// This is the same as [compress], but the compressed buffer is allocated with
+/// [compressed_buffer_size_needed] and trimmed to the size of the compressed
+/// data.
+///
+/// # Example
+///
+/// ```rust
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// let compressed = oodle_safe::compress_to_vec(
+///     oodle_safe::Compressor::Kraken,
+///     decompressed,
+///     oodle_safe::CompressionLevel::Normal,
+///     None,
+///     None,
+///     None,
+/// )
+/// .unwrap_or_else(|err| panic!("compression failed: {}", err));
+/// ```
+pub fn compress_to_vec(
+    compressor: Compressor,
+    decompressed: &[u8],
+    level: CompressionLevel,
+    options: Option<CompressOptions>,
+    dictionary_base: Option<&[u8]>,
+    scratch_memory: Option<&mut [u8]>,
+) -> Result<Vec<u8>, OodleError> {
+    let mut compressed = vec![0u8; compressed_buffer_size_needed(compressor, decompressed.len())];
+
+    let compressed_size = compress(
+        compressor,
+        decompressed,
+        &mut compressed,
+        level,
+        options,
+        dictionary_base,
+        scratch_memory,
+    )?;
+
+    compressed.truncate(compressed_size);
+    Ok(compressed)
+}
+
 /// Bool enum for the LZ decoder to check the CRC of the compressed data.
 ///
 /// To use [CheckCRC::Yes], the compressed data must have been compressed with
 /// the CRC option enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CheckCRC {
     No,
     Yes,
@@ -473,7 +1173,45 @@ impl Into<oodle_sys::OodleLZ_CheckCRC> for CheckCRC {
     }
 }
 
+impl TryFrom<oodle_sys::OodleLZ_CheckCRC> for CheckCRC {
+    type Error = OodleError;
+
+    fn try_from(check_crc: oodle_sys::OodleLZ_CheckCRC) -> Result<Self, OodleError> {
+        match check_crc {
+            oodle_sys::OodleLZ_CheckCRC_OodleLZ_CheckCRC_No => Ok(CheckCRC::No),
+            oodle_sys::OodleLZ_CheckCRC_OodleLZ_CheckCRC_Yes => Ok(CheckCRC::Yes),
+            _ => Err(OodleError::UnknownValue("check CRC")),
+        }
+    }
+}
+
+impl CheckCRC {
+    /// Iterate over both values.
+    pub fn all() -> impl Iterator<Item = Self> {
+        [CheckCRC::No, CheckCRC::Yes].into_iter()
+    }
+}
+
+impl std::fmt::Display for CheckCRC {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CheckCRC::No => "No",
+            CheckCRC::Yes => "Yes",
+        })
+    }
+}
+
+/// Parse the name given by [Display](std::fmt::Display), ignoring case.
+impl std::str::FromStr for CheckCRC {
+    type Err = OodleError;
+
+    fn from_str(name: &str) -> Result<Self, OodleError> {
+        parse_name(CheckCRC::all(), name, "check CRC")
+    }
+}
+
 /// Verbosity level for LZ decompression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Verbosity {
     /// Will not log anything, even when the decoder sees corrupted data.
     None,
@@ -499,9 +1237,60 @@ impl Into<oodle_sys::OodleLZ_Verbosity> for Verbosity {
     }
 }
 
+impl TryFrom<oodle_sys::OodleLZ_Verbosity> for Verbosity {
+    type Error = OodleError;
+
+    fn try_from(verbosity: oodle_sys::OodleLZ_Verbosity) -> Result<Self, OodleError> {
+        match verbosity {
+            oodle_sys::OodleLZ_Verbosity_OodleLZ_Verbosity_None => Ok(Verbosity::None),
+            oodle_sys::OodleLZ_Verbosity_OodleLZ_Verbosity_Minimal => Ok(Verbosity::Minimal),
+            oodle_sys::OodleLZ_Verbosity_OodleLZ_Verbosity_Some => Ok(Verbosity::Some),
+            oodle_sys::OodleLZ_Verbosity_OodleLZ_Verbosity_Lots => Ok(Verbosity::Lots),
+            _ => Err(OodleError::UnknownValue("verbosity")),
+        }
+    }
+}
+
+impl Verbosity {
+    /// Iterate over all the verbosity levels, from the quietest.
+    pub fn all() -> impl Iterator<Item = Self> {
+        [
+            Verbosity::None,
+            Verbosity::Minimal,
+            Verbosity::Some,
+            Verbosity::Lots,
+        ]
+        .into_iter()
+    }
+}
+
+impl std::fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Verbosity::None => "None",
+            Verbosity::Minimal => "Minimal",
+            Verbosity::Some => "Some",
+            Verbosity::Lots => "Lots",
+        })
+    }
+}
+
+/// Parse the name given by [Display](std::fmt::Display), ignoring case.
+impl std::str::FromStr for Verbosity {
+    type Err = OodleError;
+
+    fn from_str(name: &str) -> Result<Self, OodleError> {
+        parse_name(Verbosity::all(), name, "verbosity")
+    }
+}
+
 /// Thread phase for threaded decompression.
 ///
 /// Note that threaded decompression is only available for the Kraken compressor.
+///
+/// [DecodeThreadPhase::All] and [DecodeThreadPhase::Unthreaded] have the same
+/// value, and are equal.
+#[derive(Debug, Clone, Copy)]
 pub enum DecodeThreadPhase {
     One,
     Two,
@@ -527,6 +1316,75 @@ impl Into<oodle_sys::OodleLZ_Decode_ThreadPhase> for DecodeThreadPhase {
     }
 }
 
+impl TryFrom<oodle_sys::OodleLZ_Decode_ThreadPhase> for DecodeThreadPhase {
+    type Error = OodleError;
+
+    #[rustfmt::skip]
+    fn try_from(thread_phase: oodle_sys::OodleLZ_Decode_ThreadPhase) -> Result<Self, OodleError> {
+        match thread_phase {
+            oodle_sys::OodleLZ_Decode_ThreadPhase_OodleLZ_Decode_ThreadPhase1 => Ok(DecodeThreadPhase::One),
+            oodle_sys::OodleLZ_Decode_ThreadPhase_OodleLZ_Decode_ThreadPhase2 => Ok(DecodeThreadPhase::Two),
+            oodle_sys::OodleLZ_Decode_ThreadPhase_OodleLZ_Decode_Unthreaded => Ok(DecodeThreadPhase::Unthreaded),
+            _ => Err(OodleError::UnknownValue("decode thread phase")),
+        }
+    }
+}
+
+impl DecodeThreadPhase {
+    /// Iterate over all the thread phases, without [DecodeThreadPhase::All].
+    pub fn all() -> impl Iterator<Item = Self> {
+        [
+            DecodeThreadPhase::One,
+            DecodeThreadPhase::Two,
+            DecodeThreadPhase::Unthreaded,
+        ]
+        .into_iter()
+    }
+
+    /// Raw value of the thread phase.
+    fn value(self) -> oodle_sys::OodleLZ_Decode_ThreadPhase {
+        self.into()
+    }
+}
+
+impl PartialEq for DecodeThreadPhase {
+    fn eq(&self, other: &Self) -> bool {
+        self.value() == other.value()
+    }
+}
+
+impl Eq for DecodeThreadPhase {}
+
+impl std::hash::Hash for DecodeThreadPhase {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.value().hash(state)
+    }
+}
+
+impl std::fmt::Display for DecodeThreadPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DecodeThreadPhase::One => "One",
+            DecodeThreadPhase::Two => "Two",
+            DecodeThreadPhase::All => "All",
+            DecodeThreadPhase::Unthreaded => "Unthreaded",
+        })
+    }
+}
+
+/// Parse the name given by [Display](std::fmt::Display), ignoring case.
+impl std::str::FromStr for DecodeThreadPhase {
+    type Err = OodleError;
+
+    fn from_str(name: &str) -> Result<Self, OodleError> {
+        if name.eq_ignore_ascii_case("All") {
+            return Ok(DecodeThreadPhase::All);
+        }
+
+        parse_name(DecodeThreadPhase::all(), name, "decode thread phase")
+    }
+}
+
 /// Decompress some data from memory to memory synchronously.
 ///
 /// # Arguments
@@ -545,6 +1403,19 @@ impl Into<oodle_sys::OodleLZ_Decode_ThreadPhase> for DecodeThreadPhase {
 ///
 /// The size of the decompressed data.
 ///
+/// # Errors
+///
+/// * [OodleError::InvalidHeader] if `compressed` does not start with a valid
+///   Oodle chunk header.
+/// * [OodleError::MissingDictionary] if `compressed` was compressed with a
+///   dictionary and `dictionary_base` is `None`.
+/// * [OodleError::InvalidDictionary] if `dictionary_base` does not directly
+///   precede `decompressed` in memory, or its length is not a multiple of
+///   [BLOCK_LEN].
+/// * [OodleError::CrcMismatch] if `check_crc` is [CheckCRC::Yes] and the CRCs
+///   of the compressed data do not match.
+/// * [OodleError::CorruptData] if the data could not be decompressed.
+///
 /// # Example
 /// ```rust
 /// // Load compressed data from a file (or any other source).
@@ -562,7 +1433,7 @@ impl Into<oodle_sys::OodleLZ_Decode_ThreadPhase> for DecodeThreadPhase {
 ///     Some(oodle_safe::Verbosity::None), // same as default
 ///     Some(oodle_safe::DecodeThreadPhase::Unthreaded), // same as default
 /// )
-/// .unwrap_or_else(|_| panic!("decompression failed"));
+/// .unwrap_or_else(|err| panic!("decompression failed: {}", err));
 /// ```
 pub fn decompress(
     compressed: &[u8],
@@ -571,34 +1442,267 @@ pub fn decompress(
     check_crc: Option<CheckCRC>,
     verbosity: Option<Verbosity>,
     thread_phase: Option<DecodeThreadPhase>,
-) -> Result<usize, u32> {
+) -> Result<usize, OodleError> {
+    decompress_impl(
+        compressed,
+        decompressed,
+        dictionary_base,
+        check_crc,
+        verbosity,
+        thread_phase,
+        None,
+        None,
+    )
+}
+
+/// Decompress some data from memory to memory synchronously, reporting the
+/// progress to a callback.
+///
+/// This is the same as [decompress], but `callback` is called as the
+/// decompression proceeds, typically once per block. Returning
+/// [CallbackAction::Cancel] stops the decompression, which then returns
+/// [OodleError::Cancelled].
+///
+/// A panic inside `callback` cancels the decompression, and is resumed once
+/// Oodle has returned.
+///
+/// # Example
+///
+/// ```rust
+/// let compressed = include_bytes!("../test_data/compressed");
+/// # let decompressed_size = u32::from_le_bytes(compressed[..4].try_into().unwrap()) as usize;
+/// let mut decompressed = vec![0u8; decompressed_size];
+///
+/// oodle_safe::decompress_with_callback(
+///     &compressed[4..],
+///     &mut decompressed,
+///     None,
+///     None,
+///     None,
+///     None,
+///     |progress| {
+///         println!("{}/{}", progress.decompressed_done, progress.decompressed_len);
+///         oodle_safe::CallbackAction::Continue
+///     },
+/// )
+/// .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+/// ```
+pub fn decompress_with_callback<F>(
+    compressed: &[u8],
+    decompressed: &mut [u8],
+    dictionary_base: Option<&mut [u8]>,
+    check_crc: Option<CheckCRC>,
+    verbosity: Option<Verbosity>,
+    thread_phase: Option<DecodeThreadPhase>,
+    mut callback: F,
+) -> Result<usize, OodleError>
+where
+    F: FnMut(DecompressProgress) -> CallbackAction,
+{
+    decompress_impl(
+        compressed,
+        decompressed,
+        dictionary_base,
+        check_crc,
+        verbosity,
+        thread_phase,
+        Some(&mut callback),
+        None,
+    )
+}
+
+/// Implementation of [decompress] with an optional callback and decoder
+/// memory, which must be at least `OodleLZDecoder_MemorySizeNeeded` bytes to
+/// be used by Oodle.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn decompress_impl(
+    compressed: &[u8],
+    decompressed: &mut [u8],
+    dictionary_base: Option<&mut [u8]>,
+    check_crc: Option<CheckCRC>,
+    verbosity: Option<Verbosity>,
+    thread_phase: Option<DecodeThreadPhase>,
+    callback: Option<&mut dyn FnMut(DecompressProgress) -> CallbackAction>,
+    decoder_memory: Option<&mut [u8]>,
+) -> Result<usize, OodleError> {
+    if decompressed.is_empty() {
+        return Ok(0);
+    }
+
+    let mut independent = 0;
+    let compressor = unsafe {
+        oodle_sys::OodleLZ_GetFirstChunkCompressor(
+            compressed.as_ptr() as *const _,
+            compressed.len() as isize,
+            &mut independent,
+        )
+    };
+
+    if compressor == oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Invalid {
+        return Err(OodleError::InvalidHeader);
+    }
+
     let (dictionary_base, dictionary_base_len) = match dictionary_base {
+        Some(x) => {
+            check_dictionary(x, decompressed.as_ptr())?;
+            (x.as_mut_ptr(), (x.len() + decompressed.len()) as isize)
+        }
+        None if independent == 0 => return Err(OodleError::MissingDictionary),
+        None => (std::ptr::null_mut(), 0),
+    };
+
+    let check_crc: oodle_sys::OodleLZ_CheckCRC = check_crc.unwrap_or_default().into();
+    let verbosity: oodle_sys::OodleLZ_Verbosity = verbosity.unwrap_or_default().into();
+    let thread_phase: oodle_sys::OodleLZ_Decode_ThreadPhase =
+        thread_phase.unwrap_or_default().into();
+
+    let (decoder_memory, decoder_memory_len) = match decoder_memory {
         Some(x) => (x.as_mut_ptr(), x.len() as isize),
         None => (std::ptr::null_mut(), 0),
     };
 
-    let result = unsafe {
+    let mut callback_data = callback.map(CallbackData::new);
+
+    let (callback, callback_data_ptr) = match &mut callback_data {
+        Some(x) => (
+            Some(decompress_callback as _),
+            x as *mut CallbackData as *mut std::ffi::c_void,
+        ),
+        None => (None, std::ptr::null_mut()),
+    };
+
+    let mut decode = |check_crc: oodle_sys::OodleLZ_CheckCRC,
+                      callback: oodle_sys::OodleDecompressCallback| unsafe {
         oodle_sys::OodleLZ_Decompress(
             compressed.as_ptr() as *const _,
             compressed.len() as isize,
             decompressed.as_mut_ptr() as *mut _,
             decompressed.len() as isize,
             oodle_sys::OodleLZ_FuzzSafe_OodleLZ_FuzzSafe_Yes,
-            check_crc.unwrap_or_default().into(),
-            verbosity.unwrap_or_default().into(),
+            check_crc,
+            verbosity,
             dictionary_base as *mut _,
             dictionary_base_len,
-            None, // TODO: add callback
-            std::ptr::null_mut(),
-            std::ptr::null_mut(),
-            0,
-            thread_phase.unwrap_or_default().into(),
+            callback,
+            callback_data_ptr,
+            decoder_memory as *mut _,
+            decoder_memory_len,
+            thread_phase,
         ) as usize
     };
 
-    if result == FAILED as usize {
-        Err(FAILED)
-    } else {
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    logging::clear_assertion();
+
+    let result = decode(check_crc, callback);
+
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    logging::check_assertion()?;
+
+    if let Some(callback_data) = &mut callback_data {
+        if let Some(payload) = callback_data.panic.take() {
+            std::panic::resume_unwind(payload);
+        }
+
+        if callback_data.cancelled {
+            return Err(OodleError::Cancelled);
+        }
+    }
+
+    if result != FAILED as usize {
         Ok(result)
+    } else if check_crc == oodle_sys::OodleLZ_CheckCRC_OodleLZ_CheckCRC_Yes
+        && decode(CheckCRC::No.into(), None) != FAILED as usize
+    {
+        // The data decodes fine without checking the CRCs, so they are the
+        // reason of the failure.
+        Err(OodleError::CrcMismatch)
+    } else {
+        Err(OodleError::CorruptData)
+    }
+}
+
+/// Size of the buffer needed to decompress `decompressed_len` bytes of data
+/// compressed with `compressor`.
+///
+/// When `corruption_possible` is true, the size includes the padding needed to
+/// decode corrupted data without overrunning the buffer.
+pub fn decode_buffer_size(
+    compressor: Compressor,
+    decompressed_len: usize,
+    corruption_possible: bool,
+) -> usize {
+    unsafe {
+        oodle_sys::OodleLZ_GetDecodeBufferSize(
+            compressor.into(),
+            decompressed_len as isize,
+            if corruption_possible { 1 } else { 0 },
+        ) as usize
+    }
+}
+
+/// Largest decompressed length `compressed_len` bytes can decode to.
+///
+/// Every block of [BLOCK_LEN] bytes takes at least one byte of compressed
+/// data, which bounds the lengths read from untrusted headers before they are
+/// allocated.
+pub(crate) fn max_decompressed_len(compressed_len: usize) -> usize {
+    compressed_len.saturating_mul(BLOCK_LEN as usize)
+}
+
+/// Decompress some data from memory to a newly allocated vector.
+///
+/// This is the same as [decompress], but the decompressed buffer of
+/// `decompressed_size` bytes is allocated by the function.
+///
+/// The size of the decompressed data is not stored in the compressed data,
+/// so it must be known by the caller.
+///
+/// # Example
+///
+/// ```rust
+/// let compressed = include_bytes!("../test_data/compressed");
+/// # let decompressed_size = u32::from_le_bytes(compressed[..4].try_into().unwrap()) as usize;
+///
+/// let decompressed = oodle_safe::decompress_to_vec(
+///     &compressed[4..],
+///     decompressed_size,
+///     None,
+///     None,
+///     None,
+/// )
+/// .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+/// ```
+pub fn decompress_to_vec(
+    compressed: &[u8],
+    decompressed_size: usize,
+    check_crc: Option<CheckCRC>,
+    verbosity: Option<Verbosity>,
+    thread_phase: Option<DecodeThreadPhase>,
+) -> Result<Vec<u8>, OodleError> {
+    // Oodle decodes exactly the length it is given, so the padding of
+    // decode_buffer_size would never be used.
+    let mut decompressed = vec![0u8; decompressed_size];
+
+    decompress(
+        compressed,
+        &mut decompressed,
+        None,
+        check_crc,
+        verbosity,
+        thread_phase,
+    )?;
+
+    Ok(decompressed)
+}
+
+/// Check that `dictionary` directly precedes `data` in memory and that its
+/// length is a multiple of [BLOCK_LEN], as required by Oodle.
+fn check_dictionary(dictionary: &[u8], data: *const u8) -> Result<(), OodleError> {
+    if dictionary.as_ptr_range().end != data || !dictionary.len().is_multiple_of(BLOCK_LEN as usize)
+    {
+        Err(OodleError::InvalidDictionary)
+    } else {
+        Ok(())
     }
 }
@@ -0,0 +1,190 @@
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Once;
+
+use crate::{OodleError, Verbosity};
+
+extern "C" {
+    /// Printf plugin formatting the message and passing it to
+    /// [oodle_safe_log_message], defined in `printf.c`.
+    fn oodle_safe_printf(level: c_int, file: *const c_char, line: c_int, fmt: *const c_char, ...);
+}
+
+/// What to do when Oodle raises an assertion.
+///
+/// Assertions are only raised by debug builds of Oodle. They are always
+/// logged at the error level. Assertions raised from other threads than the
+/// one calling Oodle, like jobs, are only logged.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AssertionAction {
+    /// Only log the assertion.
+    #[default]
+    Log,
+
+    /// Fail the call with [OodleError::AssertionFailed].
+    Error,
+
+    /// Panic once the call returns.
+    Panic,
+}
+
+/// Assertion raised by Oodle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assertion {
+    pub file: String,
+    pub line: u32,
+    pub function: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Assertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: ", self.file, self.line)?;
+        if let Some(function) = &self.function {
+            write!(f, "{}: ", function)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+static ASSERTION_ACTION: AtomicU8 = AtomicU8::new(AssertionAction::Log as u8);
+
+thread_local! {
+    /// Last assertion raised on this thread, not handled yet.
+    static ASSERTION: RefCell<Option<Assertion>> = const { RefCell::new(None) };
+}
+
+/// Forward the messages and assertions of Oodle to the `log` or `tracing`
+/// crates, with the `log` and `tracing` features.
+///
+/// The messages are logged with the `oodle` target, at the info, debug and
+/// trace levels for [Verbosity::Minimal], [Verbosity::Some] and
+/// [Verbosity::Lots]. With both features, they only go to `tracing`.
+///
+/// The plugins are installed on the first call, which should be done at
+/// startup as Oodle does not install them in a thread-safe way. Later calls
+/// only change the [AssertionAction].
+pub fn install_logger(assertion_action: AssertionAction) {
+    ASSERTION_ACTION.store(assertion_action as u8, Ordering::Relaxed);
+
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| unsafe {
+        oodle_sys::OodleCore_Plugins_SetPrintf(Some(oodle_safe_printf));
+        oodle_sys::OodleCore_Plugins_SetAssertion(Some(display_assertion));
+    });
+}
+
+/// Take the last assertion raised by Oodle on this thread, if any.
+///
+/// This gives the details of an [OodleError::AssertionFailed] error. The
+/// assertion is forgotten when the next call to Oodle starts.
+pub fn take_assertion() -> Option<Assertion> {
+    ASSERTION.with(|x| x.borrow_mut().take())
+}
+
+/// Forget the assertion left by an earlier call to Oodle on this thread, so
+/// that it is not reported by the next [check_assertion].
+pub(crate) fn clear_assertion() {
+    ASSERTION.with(|x| x.borrow_mut().take());
+}
+
+/// Apply the [AssertionAction] to the assertions raised on this thread during
+/// the last call to Oodle.
+pub(crate) fn check_assertion() -> Result<(), OodleError> {
+    let raised = ASSERTION.with(|x| x.borrow().is_some());
+    if !raised {
+        return Ok(());
+    }
+
+    match ASSERTION_ACTION.load(Ordering::Relaxed) {
+        x if x == AssertionAction::Error as u8 => Err(OodleError::AssertionFailed),
+        x if x == AssertionAction::Panic as u8 => {
+            panic!("oodle assertion failed: {}", take_assertion().unwrap())
+        }
+        _ => {
+            take_assertion();
+            Ok(())
+        }
+    }
+}
+
+/// Verbosity from which Oodle prints messages of `level`.
+fn verbosity(level: c_int) -> Verbosity {
+    match level {
+        ..=0 => Verbosity::Minimal,
+        1 => Verbosity::Some,
+        _ => Verbosity::Lots,
+    }
+}
+
+unsafe fn to_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn oodle_safe_log_message(
+    level: c_int,
+    file: *const c_char,
+    line: c_int,
+    message: *const c_char,
+) {
+    let file = to_string(file).unwrap_or_default();
+    let message = to_string(message).unwrap_or_default();
+    let message = message.trim_end();
+
+    #[cfg(feature = "tracing")]
+    match verbosity(level) {
+        Verbosity::Minimal => tracing::info!(target: "oodle", file, line, "{}", message),
+        Verbosity::Some => tracing::debug!(target: "oodle", file, line, "{}", message),
+        _ => tracing::trace!(target: "oodle", file, line, "{}", message),
+    }
+
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    {
+        let level = match verbosity(level) {
+            Verbosity::Minimal => log::Level::Info,
+            Verbosity::Some => log::Level::Debug,
+            _ => log::Level::Trace,
+        };
+
+        log::logger().log(
+            &log::Record::builder()
+                .args(format_args!("{}", message))
+                .level(level)
+                .target("oodle")
+                .file(Some(&file))
+                .line(Some(line as u32))
+                .build(),
+        );
+    }
+}
+
+pub(crate) unsafe extern "C" fn display_assertion(
+    file: *const c_char,
+    line: c_int,
+    function: *const c_char,
+    message: *const c_char,
+) -> oodle_sys::OO_BOOL {
+    let assertion = Assertion {
+        file: to_string(file).unwrap_or_default(),
+        line: line as u32,
+        function: to_string(function),
+        message: to_string(message).unwrap_or_default(),
+    };
+
+    #[cfg(feature = "tracing")]
+    tracing::error!(target: "oodle", "assertion failed: {}", assertion);
+
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    log::error!(target: "oodle", "assertion failed: {}", assertion);
+
+    ASSERTION.with(|x| *x.borrow_mut() = Some(assertion));
+
+    // Never break into the debugger, the assertion is handled after the call.
+    0
+}
@@ -0,0 +1,164 @@
+use rayon::prelude::*;
+
+use crate::{
+    compress_to_vec, decompress, CheckCRC, CompressOptions, CompressionLevel, Compressor,
+    OodleError, SeekTable, BLOCK_LEN,
+};
+
+/// Number of seek chunks per thread, so that the work stays balanced when some
+/// chunks compress slower than others.
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// Compress some data on the rayon thread pool.
+///
+/// The data is split into independent seek chunks which are compressed
+/// concurrently, then concatenated into a single stream that [decompress](crate::decompress)
+/// accepts as is.
+///
+/// The seek chunk length is picked with `OodleLZ_MakeSeekChunkLen` from the
+/// number of threads of the pool, and is at least the `seek_chunk_len` of
+/// `options`. `seek_chunk_reset` is always enabled.
+///
+/// # Example
+///
+/// ```rust
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// let compressed = oodle_safe::compress_parallel(
+///     oodle_safe::Compressor::Kraken,
+///     decompressed,
+///     oodle_safe::CompressionLevel::Optimal3,
+///     None,
+/// )
+/// .unwrap_or_else(|err| panic!("compression failed: {}", err));
+/// ```
+pub fn compress_parallel(
+    compressor: Compressor,
+    decompressed: &[u8],
+    level: CompressionLevel,
+    options: Option<CompressOptions>,
+) -> Result<Vec<u8>, OodleError> {
+    let mut options = options.unwrap_or_else(|| CompressOptions::for_compressor(compressor, level));
+    options.check()?;
+
+    let num_chunks = rayon::current_num_threads() * CHUNKS_PER_THREAD;
+    let seek_chunk_len = unsafe {
+        oodle_sys::OodleLZ_MakeSeekChunkLen(decompressed.len() as i64, num_chunks as i32)
+    } as u32;
+
+    options.seek_chunk_len = options.seek_chunk_len.max(seek_chunk_len).max(BLOCK_LEN);
+    options.seek_chunk_reset = true;
+
+    let chunk_len = options.seek_chunk_len as usize;
+
+    let chunks = decompressed
+        .par_chunks(chunk_len)
+        .map(|chunk| compress_to_vec(compressor, chunk, level, Some(options), None, None))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(chunks.concat())
+}
+
+/// Decompress some data on the rayon thread pool.
+///
+/// The compressed data is split at the start of each independent chunk, and
+/// the chunks are decoded concurrently into disjoint parts of `decompressed`.
+/// This is only faster for data compressed with `seek_chunk_reset`, for
+/// example with [compress_parallel]. Other data is decoded on a single thread.
+///
+/// Returns the number of bytes written to `decompressed`, which must be the
+/// exact size of the decompressed data.
+///
+/// # Example
+///
+/// ```rust
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// let compressed = oodle_safe::compress_parallel(
+///     oodle_safe::Compressor::Kraken,
+///     decompressed,
+///     oodle_safe::CompressionLevel::Normal,
+///     None,
+/// )
+/// .unwrap_or_else(|err| panic!("compression failed: {}", err));
+///
+/// let mut output = vec![0u8; decompressed.len()];
+///
+/// oodle_safe::decompress_parallel(&compressed, &mut output, None)
+///     .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+/// ```
+pub fn decompress_parallel(
+    compressed: &[u8],
+    decompressed: &mut [u8],
+    check_crc: Option<CheckCRC>,
+) -> Result<usize, OodleError> {
+    if decompressed.is_empty() {
+        return Ok(0);
+    }
+
+    let mut jobs = Vec::new();
+    let mut compressed_rest = compressed;
+    let mut decompressed_rest = decompressed;
+
+    for (compressed_len, decompressed_len) in
+        independent_chunks(compressed, decompressed_rest.len())?
+    {
+        let (compressed_chunk, x) = compressed_rest.split_at(compressed_len);
+        compressed_rest = x;
+
+        let (decompressed_chunk, x) = decompressed_rest.split_at_mut(decompressed_len);
+        decompressed_rest = x;
+
+        jobs.push((compressed_chunk, decompressed_chunk));
+    }
+
+    jobs.into_par_iter()
+        .map(|(compressed, decompressed)| {
+            let len = decompress(compressed, decompressed, None, check_crc, None, None)?;
+            if len != decompressed.len() {
+                return Err(OodleError::CorruptData);
+            }
+            Ok(len)
+        })
+        .sum()
+}
+
+/// Compressed and decompressed lengths of the runs of blocks that can be
+/// decoded independently of the previous ones.
+fn independent_chunks(
+    compressed: &[u8],
+    decompressed_len: usize,
+) -> Result<Vec<(usize, usize)>, OodleError> {
+    let blocks = SeekTable::new(compressed, decompressed_len as u64, BLOCK_LEN)?;
+    let mut chunks: Vec<(usize, usize)> = Vec::new();
+
+    for entry in 0..blocks.num_seek_chunks() {
+        let compressed_range = blocks.compressed_range(entry);
+        let decompressed_range = blocks.decompressed_range(entry);
+        let compressed_len = (compressed_range.end - compressed_range.start) as usize;
+        let decompressed_len = (decompressed_range.end - decompressed_range.start) as usize;
+
+        let mut independent = 0;
+        let compressor = unsafe {
+            oodle_sys::OodleLZ_GetFirstChunkCompressor(
+                compressed[compressed_range.start as usize..].as_ptr() as *const _,
+                (compressed.len() - compressed_range.start as usize) as isize,
+                &mut independent,
+            )
+        };
+
+        if compressor == oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Invalid {
+            return Err(OodleError::CorruptData);
+        }
+
+        match chunks.last_mut() {
+            Some(last) if independent == 0 => {
+                last.0 += compressed_len;
+                last.1 += decompressed_len;
+            }
+            _ => chunks.push((compressed_len, decompressed_len)),
+        }
+    }
+
+    Ok(chunks)
+}
@@ -0,0 +1,41 @@
+// Printf plugin of Oodle, formatting its messages for the Rust logger.
+//
+// Oodle calls its printf plugin with C varargs, which stable Rust cannot
+// receive, so the message is formatted here.
+
+#include <stdarg.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+void oodle_safe_log_message(int level, const char *file, int line, const char *message);
+
+void oodle_safe_printf(int level, const char *file, int line, const char *fmt, ...)
+{
+    char buffer[512];
+    char *message = buffer;
+    va_list args, args_copy;
+
+    va_start(args, fmt);
+    va_copy(args_copy, args);
+
+    int len = vsnprintf(buffer, sizeof(buffer), fmt, args);
+    if (len >= (int)sizeof(buffer)) {
+        message = malloc((size_t)len + 1);
+        if (message != NULL) {
+            vsnprintf(message, (size_t)len + 1, fmt, args_copy);
+        } else {
+            message = buffer;
+        }
+    }
+
+    va_end(args_copy);
+    va_end(args);
+
+    if (len >= 0) {
+        oodle_safe_log_message(level, file, line, message);
+    }
+
+    if (message != buffer) {
+        free(message);
+    }
+}
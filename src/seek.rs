@@ -0,0 +1,437 @@
+use std::ops::Range;
+
+use crate::bytes::ByteReader;
+use crate::{
+    compress_to_vec, CompressOptions, CompressionLevel, Compressor, OodleError, BLOCK_LEN,
+};
+
+/// Magic number of serialized seek tables.
+const MAGIC: [u8; 4] = *b"OOST";
+
+/// Version of the serialized seek table format.
+const VERSION: u8 = 1;
+
+const FLAG_INDEPENDENT: u8 = 1 << 0;
+const FLAG_RAW_CRCS: u8 = 1 << 1;
+
+/// Map of the seek chunks of a compressed stream.
+///
+/// The seek table gives the compressed position of each seek chunk, so that
+/// the chunk containing a decompressed position can be found without decoding
+/// the data before it. When the stream was compressed with
+/// `seek_chunk_reset`, the chunks are independent and can be decompressed on
+/// their own.
+///
+/// A seek table can optionally hold a CRC of the decompressed data of each
+/// chunk, and can be serialized with [SeekTable::to_bytes] to be stored next
+/// to the compressed data.
+///
+/// # Example
+///
+/// ```rust
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// let (compressed, seek_table) = oodle_safe::compress_with_seek_table(
+///     oodle_safe::Compressor::Kraken,
+///     decompressed,
+///     oodle_safe::CompressionLevel::Normal,
+///     None,
+/// )
+/// .unwrap_or_else(|err| panic!("compression failed: {}", err));
+///
+/// let bytes = seek_table.to_bytes();
+/// let seek_table = oodle_safe::SeekTable::from_bytes(&bytes).unwrap();
+///
+/// assert_eq!(seek_table.find_seek_entry(1000), Some(0));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeekTable {
+    compressor: oodle_sys::OodleLZ_Compressor,
+    independent: bool,
+    decompressed_len: u64,
+    seek_chunk_len: u32,
+    compressed_lens: Vec<u32>,
+    raw_crcs: Option<Vec<u32>>,
+}
+
+impl SeekTable {
+    /// Build the seek table of `compressed` by scanning its chunk headers.
+    ///
+    /// `seek_chunk_len` must be the one used for compression, or a power of 2
+    /// multiple of it.
+    pub fn new(
+        compressed: &[u8],
+        decompressed_len: u64,
+        seek_chunk_len: u32,
+    ) -> Result<Self, OodleError> {
+        Self::create(compressed, None, decompressed_len, seek_chunk_len)
+    }
+
+    /// Build the seek table of `compressed`, with CRCs of the chunks of
+    /// `decompressed`.
+    ///
+    /// `seek_chunk_len` must be the one used for compression, or a power of 2
+    /// multiple of it.
+    pub fn with_crcs(
+        compressed: &[u8],
+        decompressed: &[u8],
+        seek_chunk_len: u32,
+    ) -> Result<Self, OodleError> {
+        Self::create(
+            compressed,
+            Some(decompressed),
+            decompressed.len() as u64,
+            seek_chunk_len,
+        )
+    }
+
+    fn create(
+        compressed: &[u8],
+        decompressed: Option<&[u8]>,
+        decompressed_len: u64,
+        seek_chunk_len: u32,
+    ) -> Result<Self, OodleError> {
+        // Same constraints as CompressOptions::seek_chunk_len, which tables
+        // read by SeekTable::from_bytes are also held to.
+        if !seek_chunk_len.is_power_of_two()
+            || !(BLOCK_LEN..=oodle_sys::OODLELZ_SEEKCHUNKLEN_MAX).contains(&seek_chunk_len)
+        {
+            return Err(OodleError::InvalidOptions(
+                "seek_chunk_len must be a power of 2 between BLOCK_LEN and OODLELZ_SEEKCHUNKLEN_MAX",
+            ));
+        }
+
+        let (flags, raw_buf) = match decompressed {
+            Some(x) => (
+                oodle_sys::OodleLZSeekTable_Flags_OodleLZSeekTable_Flags_MakeRawCRCs,
+                x.as_ptr(),
+            ),
+            None => (
+                oodle_sys::OodleLZSeekTable_Flags_OodleLZSeekTable_Flags_None,
+                std::ptr::null(),
+            ),
+        };
+
+        let table = unsafe {
+            oodle_sys::OodleLZ_CreateSeekTable(
+                flags,
+                seek_chunk_len as i32,
+                raw_buf as *const _,
+                decompressed_len as isize,
+                compressed.as_ptr() as *const _,
+                compressed.len() as isize,
+            )
+        };
+
+        if table.is_null() {
+            return Err(OodleError::CorruptData);
+        }
+
+        let raw = unsafe { *table };
+        let num_seek_chunks = raw.numSeekChunks as usize;
+
+        let compressed_lens =
+            unsafe { std::slice::from_raw_parts(raw.seekChunkCompLens, num_seek_chunks) }.to_vec();
+
+        let raw_crcs = if raw.rawCRCs.is_null() {
+            None
+        } else {
+            Some(unsafe { std::slice::from_raw_parts(raw.rawCRCs, num_seek_chunks) }.to_vec())
+        };
+
+        unsafe { oodle_sys::OodleLZ_FreeSeekTable(table) };
+
+        Ok(Self {
+            compressor: raw.compressor,
+            independent: raw.seekChunksIndependent != 0,
+            decompressed_len: raw.totalRawLen as u64,
+            seek_chunk_len: raw.seekChunkLen as u32,
+            compressed_lens,
+            raw_crcs,
+        })
+    }
+
+    /// View of the table as an Oodle seek table, valid as long as `self`.
+    fn as_raw(&self) -> oodle_sys::OodleLZ_SeekTable {
+        oodle_sys::OodleLZ_SeekTable {
+            compressor: self.compressor,
+            seekChunksIndependent: if self.independent { 1 } else { 0 },
+            totalRawLen: self.decompressed_len as i64,
+            totalCompLen: self.compressed_len() as i64,
+            numSeekChunks: self.compressed_lens.len() as i32,
+            seekChunkLen: self.seek_chunk_len as i32,
+            seekChunkCompLens: self.compressed_lens.as_ptr() as *mut _,
+            rawCRCs: match &self.raw_crcs {
+                Some(x) => x.as_ptr() as *mut _,
+                None => std::ptr::null_mut(),
+            },
+        }
+    }
+
+    /// Compressor used for the stream, or `None` if different compressors
+    /// were used for its chunks.
+    pub fn compressor(&self) -> Option<Compressor> {
+        Compressor::try_from(self.compressor).ok()
+    }
+
+    /// Whether the seek chunks can be decompressed independently.
+    pub fn is_independent(&self) -> bool {
+        self.independent
+    }
+
+    /// Total size of the decompressed data.
+    pub fn decompressed_len(&self) -> u64 {
+        self.decompressed_len
+    }
+
+    /// Total size of the compressed data.
+    pub fn compressed_len(&self) -> u64 {
+        self.compressed_lens.iter().map(|&x| x as u64).sum()
+    }
+
+    /// Decompressed length of every seek chunk but the last one.
+    pub fn seek_chunk_len(&self) -> u32 {
+        self.seek_chunk_len
+    }
+
+    /// Number of seek chunks.
+    pub fn num_seek_chunks(&self) -> usize {
+        self.compressed_lens.len()
+    }
+
+    /// Whether the table holds CRCs of the decompressed chunks.
+    pub fn has_crcs(&self) -> bool {
+        self.raw_crcs.is_some()
+    }
+
+    /// Index of the seek chunk containing the decompressed position
+    /// `decompressed_pos`, or `None` if it is past the end of the data.
+    pub fn find_seek_entry(&self, decompressed_pos: u64) -> Option<usize> {
+        if decompressed_pos >= self.decompressed_len {
+            return None;
+        }
+
+        let raw = self.as_raw();
+        let entry = unsafe { oodle_sys::OodleLZ_FindSeekEntry(decompressed_pos as i64, &raw) };
+
+        Some(entry as usize)
+    }
+
+    /// Compressed position of the start of the seek chunk `entry`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `entry` is not less than [SeekTable::num_seek_chunks].
+    pub fn packed_pos(&self, entry: usize) -> u64 {
+        assert!(entry < self.num_seek_chunks(), "seek entry out of range");
+
+        let raw = self.as_raw();
+        unsafe { oodle_sys::OodleLZ_GetSeekEntryPackedPos(entry as i32, &raw) as u64 }
+    }
+
+    /// Decompressed range of the seek chunk `entry`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `entry` is not less than [SeekTable::num_seek_chunks].
+    pub fn decompressed_range(&self, entry: usize) -> Range<u64> {
+        assert!(entry < self.num_seek_chunks(), "seek entry out of range");
+
+        let start = entry as u64 * self.seek_chunk_len as u64;
+        let end = (start + self.seek_chunk_len as u64).min(self.decompressed_len);
+        start..end
+    }
+
+    /// Compressed range of the seek chunk `entry`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `entry` is not less than [SeekTable::num_seek_chunks].
+    pub fn compressed_range(&self, entry: usize) -> Range<u64> {
+        let start = self.packed_pos(entry);
+        start..start + self.compressed_lens[entry] as u64
+    }
+
+    /// Check the decompressed data against the CRCs of the table.
+    ///
+    /// Returns [OodleError::CrcMismatch] if a chunk does not match. Nothing
+    /// is checked if the table holds no CRCs.
+    pub fn check_crcs(&self, decompressed: &[u8]) -> Result<(), OodleError> {
+        if self.raw_crcs.is_none() {
+            return Ok(());
+        }
+
+        if decompressed.len() as u64 != self.decompressed_len {
+            return Err(OodleError::CrcMismatch);
+        }
+
+        let raw = self.as_raw();
+        let result = unsafe {
+            oodle_sys::OodleLZ_CheckSeekTableCRCs(
+                decompressed.as_ptr() as *const _,
+                decompressed.len() as isize,
+                &raw,
+            )
+        };
+
+        if result != 0 {
+            Ok(())
+        } else {
+            Err(OodleError::CrcMismatch)
+        }
+    }
+
+    /// Check the decompressed data of the seek chunk `entry` against its CRC.
+    ///
+    /// Returns [OodleError::CrcMismatch] if the chunk does not match. Nothing
+    /// is checked if the table holds no CRCs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `entry` is not less than [SeekTable::num_seek_chunks].
+    pub fn check_chunk_crc(&self, entry: usize, chunk: &[u8]) -> Result<(), OodleError> {
+        let range = self.decompressed_range(entry);
+
+        let Some(raw_crcs) = &self.raw_crcs else {
+            return Ok(());
+        };
+
+        if chunk.len() as u64 != range.end - range.start {
+            return Err(OodleError::CrcMismatch);
+        }
+
+        // A table with only this chunk, to check it on its own.
+        let raw = oodle_sys::OodleLZ_SeekTable {
+            totalRawLen: chunk.len() as i64,
+            totalCompLen: self.compressed_lens[entry] as i64,
+            numSeekChunks: 1,
+            seekChunkCompLens: &self.compressed_lens[entry] as *const _ as *mut _,
+            rawCRCs: &raw_crcs[entry] as *const _ as *mut _,
+            ..self.as_raw()
+        };
+
+        let result = unsafe {
+            oodle_sys::OodleLZ_CheckSeekTableCRCs(
+                chunk.as_ptr() as *const _,
+                chunk.len() as isize,
+                &raw,
+            )
+        };
+
+        if result != 0 {
+            Ok(())
+        } else {
+            Err(OodleError::CrcMismatch)
+        }
+    }
+
+    /// Serialize the table to a compact little-endian byte form.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.independent {
+            flags |= FLAG_INDEPENDENT;
+        }
+        if self.raw_crcs.is_some() {
+            flags |= FLAG_RAW_CRCS;
+        }
+
+        let mut bytes = Vec::with_capacity(26 + self.compressed_lens.len() * 8);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.push(flags);
+        bytes.extend_from_slice(&self.compressor.to_le_bytes());
+        bytes.extend_from_slice(&self.decompressed_len.to_le_bytes());
+        bytes.extend_from_slice(&self.seek_chunk_len.to_le_bytes());
+        bytes.extend_from_slice(&(self.compressed_lens.len() as u32).to_le_bytes());
+
+        for x in &self.compressed_lens {
+            bytes.extend_from_slice(&x.to_le_bytes());
+        }
+
+        for x in self.raw_crcs.iter().flatten() {
+            bytes.extend_from_slice(&x.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Deserialize a table written by [SeekTable::to_bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OodleError> {
+        let mut reader = ByteReader::new(bytes);
+
+        if reader.array::<4>()? != MAGIC {
+            return Err(OodleError::InvalidFormat("bad seek table magic"));
+        }
+
+        if reader.u8()? != VERSION {
+            return Err(OodleError::InvalidFormat("unsupported seek table version"));
+        }
+
+        let flags = reader.u8()?;
+        let compressor = reader.i32()?;
+        let decompressed_len = reader.u64()?;
+        let seek_chunk_len = reader.u32()?;
+        let num_seek_chunks = reader.u32()? as usize;
+
+        // Same constraints as CompressOptions::seek_chunk_len, so that the
+        // length stays positive as the i32 given to Oodle.
+        if !seek_chunk_len.is_power_of_two()
+            || !(BLOCK_LEN..=oodle_sys::OODLELZ_SEEKCHUNKLEN_MAX).contains(&seek_chunk_len)
+            || decompressed_len.div_ceil(seek_chunk_len as u64) != num_seek_chunks as u64
+        {
+            return Err(OodleError::InvalidFormat("inconsistent seek table"));
+        }
+
+        let has_crcs = flags & FLAG_RAW_CRCS != 0;
+        let expected_len = num_seek_chunks * if has_crcs { 8 } else { 4 };
+        if reader.remaining() != expected_len {
+            return Err(OodleError::InvalidFormat("bad seek table length"));
+        }
+
+        let compressed_lens = (0..num_seek_chunks)
+            .map(|_| reader.u32())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let raw_crcs = if has_crcs {
+            Some(
+                (0..num_seek_chunks)
+                    .map(|_| reader.u32())
+                    .collect::<Result<Vec<_>, _>>()?,
+            )
+        } else {
+            None
+        };
+
+        Ok(Self {
+            compressor,
+            independent: flags & FLAG_INDEPENDENT != 0,
+            decompressed_len,
+            seek_chunk_len,
+            compressed_lens,
+            raw_crcs,
+        })
+    }
+}
+
+/// Compress some data with independent seek chunks, and build its seek table
+/// with CRCs of the decompressed chunks.
+///
+/// `seek_chunk_reset` is enabled on `options`, which default to
+/// [CompressOptions::for_compressor]. See [compress](crate::compress).
+pub fn compress_with_seek_table(
+    compressor: Compressor,
+    decompressed: &[u8],
+    level: CompressionLevel,
+    options: Option<CompressOptions>,
+) -> Result<(Vec<u8>, SeekTable), OodleError> {
+    let mut options = options.unwrap_or_else(|| CompressOptions::for_compressor(compressor, level));
+    if options.seek_chunk_len == 0 {
+        options.seek_chunk_len = BLOCK_LEN;
+    }
+    options.seek_chunk_reset = true;
+
+    let compressed = compress_to_vec(compressor, decompressed, level, Some(options), None, None)?;
+    let seek_table = SeekTable::with_crcs(&compressed, decompressed, options.seek_chunk_len)?;
+
+    Ok((compressed, seek_table))
+}
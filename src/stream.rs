@@ -0,0 +1,612 @@
+use std::collections::VecDeque;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::{
+    CheckCRC, CompressOptions, CompressionLevel, Compressor, Encoder, OodleError, SeekTable,
+    BLOCK_LEN,
+};
+
+/// Size of the compressed data read at once from the inner reader.
+const READ_CHUNK_LEN: usize = oodle_sys::OODLELZ_BLOCK_MAX_COMPLEN as usize;
+
+/// Streaming decompressor reading compressed data from any [Read] source.
+///
+/// The data is decoded incrementally with `OodleLZDecoder_DecodeSome` into a
+/// bounded sliding window, so that the whole compressed and decompressed data
+/// never has to be held in memory.
+///
+/// The window must cover the farthest match offset of the compressed data.
+/// This is the `dictionary_size` used for compression, or the `seek_chunk_len`
+/// if `seek_chunk_reset` was enabled. When unknown, the decompressed length
+/// must be used, which makes the window as large as the data.
+///
+/// # Example
+///
+/// ```rust
+/// use std::io::Read;
+///
+/// let compressed = include_bytes!("../test_data/compressed");
+/// # let decompressed_size = u32::from_le_bytes(compressed[..4].try_into().unwrap()) as usize;
+///
+/// let mut reader = oodle_safe::DecompressReader::new(
+///     &compressed[4..],
+///     decompressed_size as u64,
+///     decompressed_size,
+/// )
+/// .unwrap();
+///
+/// let mut decompressed = Vec::new();
+/// reader.read_to_end(&mut decompressed).unwrap();
+/// ```
+pub struct DecompressReader<R: Read> {
+    inner: R,
+    decoder: *mut oodle_sys::OodleLZDecoder,
+    check_crc: CheckCRC,
+
+    /// Total size of the decompressed data.
+    decompressed_len: u64,
+
+    /// Farthest match offset kept in the window.
+    window_len: usize,
+
+    /// Decompressed data, starting at the absolute position `window_base`.
+    window: Vec<u8>,
+    window_base: u64,
+
+    /// Absolute position of the next byte to decode.
+    decoded_pos: u64,
+
+    /// Absolute position of the next byte to return to the reader.
+    read_pos: u64,
+
+    /// Compressed data read from `inner` but not decoded yet.
+    compressed: Vec<u8>,
+    compressed_start: usize,
+    compressed_end: usize,
+}
+
+impl<R: Read> DecompressReader<R> {
+    /// Create a reader decompressing `decompressed_len` bytes from `inner`,
+    /// keeping at most `window_len` bytes of decompressed data for matches.
+    ///
+    /// Returns [OodleError::DecoderCreationFailed] if Oodle cannot create a
+    /// decoder for `decompressed_len` bytes.
+    pub fn new(inner: R, decompressed_len: u64, window_len: usize) -> Result<Self, OodleError> {
+        let decoder = unsafe {
+            oodle_sys::OodleLZDecoder_Create(
+                oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Invalid,
+                decompressed_len as i64,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+
+        if decoder.is_null() {
+            return Err(OodleError::DecoderCreationFailed);
+        }
+
+        let window_size = (window_len as u64 + BLOCK_LEN as u64).min(decompressed_len) as usize;
+
+        Ok(Self {
+            inner,
+            decoder,
+            check_crc: CheckCRC::No,
+            decompressed_len,
+            window_len,
+            window: vec![0u8; window_size],
+            window_base: 0,
+            decoded_pos: 0,
+            read_pos: 0,
+            compressed: vec![0u8; READ_CHUNK_LEN],
+            compressed_start: 0,
+            compressed_end: 0,
+        })
+    }
+
+    /// Whether to check the quantum CRCs of the compressed data.
+    pub fn set_check_crc(&mut self, check_crc: CheckCRC) {
+        self.check_crc = check_crc;
+    }
+
+    /// Total size of the decompressed data.
+    pub fn decompressed_len(&self) -> u64 {
+        self.decompressed_len
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Read compressed data from the inner reader until at least `needed`
+    /// bytes are available, or at least one more byte if `needed` is already
+    /// available.
+    fn fill(&mut self, needed: usize) -> std::io::Result<()> {
+        let available = self.compressed_end - self.compressed_start;
+        let needed = needed.max(available + 1);
+
+        self.compressed
+            .copy_within(self.compressed_start..self.compressed_end, 0);
+        self.compressed_start = 0;
+        self.compressed_end = available;
+
+        if self.compressed.len() < needed {
+            self.compressed.resize(needed.max(READ_CHUNK_LEN), 0);
+        }
+
+        while self.compressed_end < needed {
+            let read = self
+                .inner
+                .read(&mut self.compressed[self.compressed_end..])?;
+            if read == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            self.compressed_end += read;
+        }
+
+        Ok(())
+    }
+
+    /// Make room for at least one more quantum in the window, keeping the
+    /// last `window_len` bytes for matches.
+    fn slide(&mut self) {
+        let pos = (self.decoded_pos - self.window_base) as usize;
+        let needed = (self.decompressed_len - self.decoded_pos).min(BLOCK_LEN as u64) as usize;
+
+        if self.window.len() - pos >= needed {
+            return;
+        }
+
+        let keep = pos.min(self.window_len);
+        self.window.copy_within(pos - keep..pos, 0);
+        self.window_base = self.decoded_pos - keep as u64;
+    }
+
+    /// Decode some more data into the window.
+    fn decode_some(&mut self) -> std::io::Result<()> {
+        loop {
+            if self.compressed_start == self.compressed_end {
+                self.fill(0)?;
+            }
+
+            self.slide();
+
+            let pos = (self.decoded_pos - self.window_base) as usize;
+            let compressed = &self.compressed[self.compressed_start..self.compressed_end];
+
+            let mut out = oodle_sys::OodleLZ_DecodeSome_Out {
+                decodedCount: 0,
+                compBufUsed: 0,
+                curQuantumRawLen: 0,
+                curQuantumCompLen: 0,
+            };
+
+            // The window is addressed with absolute positions, so that Oodle
+            // sees the whole decompressed buffer while only the window is
+            // accessed.
+            let result = unsafe {
+                oodle_sys::OodleLZDecoder_DecodeSome(
+                    self.decoder,
+                    &mut out,
+                    self.window
+                        .as_mut_ptr()
+                        .wrapping_sub(self.window_base as usize) as *mut _,
+                    self.decoded_pos as isize,
+                    self.decompressed_len as isize,
+                    (self.window.len() - pos) as isize,
+                    compressed.as_ptr() as *const _,
+                    compressed.len() as isize,
+                    oodle_sys::OodleLZ_FuzzSafe_OodleLZ_FuzzSafe_Yes,
+                    self.check_crc.into(),
+                    oodle_sys::OodleLZ_Verbosity_OodleLZ_Verbosity_None,
+                    oodle_sys::OodleLZ_Decode_ThreadPhase_OodleLZ_Decode_Unthreaded,
+                )
+            };
+
+            if result == 0 {
+                return Err(OodleError::CorruptData.into());
+            }
+
+            self.compressed_start += out.compBufUsed as usize;
+            self.decoded_pos += out.decodedCount as u64;
+
+            if out.decodedCount > 0 {
+                return Ok(());
+            }
+
+            // Nothing was decoded, more compressed data is needed.
+            let available = self.compressed_end - self.compressed_start;
+            if available >= out.curQuantumCompLen as usize && out.curQuantumCompLen > 0 {
+                return Err(OodleError::CorruptData.into());
+            }
+
+            self.fill(out.curQuantumCompLen as usize)?;
+        }
+    }
+}
+
+impl<R: Read> Read for DecompressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.read_pos == self.decoded_pos {
+            if self.decoded_pos == self.decompressed_len || buf.is_empty() {
+                return Ok(0);
+            }
+
+            self.decode_some()?;
+        }
+
+        let start = (self.read_pos - self.window_base) as usize;
+        let len = ((self.decoded_pos - self.read_pos) as usize).min(buf.len());
+
+        buf[..len].copy_from_slice(&self.window[start..start + len]);
+        self.read_pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<R: Read> Drop for DecompressReader<R> {
+    fn drop(&mut self) {
+        unsafe { oodle_sys::OodleLZDecoder_Destroy(self.decoder) };
+    }
+}
+
+// The decoder is owned by the reader and only used through `&mut self`.
+unsafe impl<R: Read + Send> Send for DecompressReader<R> {}
+
+/// Streaming compressor writing compressed data to any [Write] sink.
+///
+/// Incoming data is buffered into blocks of `seek_chunk_len` bytes, and each
+/// full block is compressed as an independent seek chunk when more data comes
+/// in. The compressed blocks form a single Oodle stream that can be
+/// decompressed with [decompress](crate::decompress) or [DecompressReader].
+///
+/// The stream must be completed with [CompressWriter::finish], which
+/// compresses the last block. The decompressed length needed to decode the
+/// stream is given by [CompressWriter::decompressed_len].
+///
+/// # Example
+///
+/// ```rust
+/// use std::io::Write;
+///
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// let mut writer = oodle_safe::CompressWriter::new(
+///     Vec::new(),
+///     oodle_safe::Compressor::Kraken,
+///     oodle_safe::CompressionLevel::Normal,
+///     None,
+/// )
+/// .unwrap_or_else(|err| panic!("invalid writer: {}", err));
+///
+/// writer.write_all(decompressed).unwrap();
+/// let decompressed_len = writer.decompressed_len();
+/// let compressed = writer.finish().unwrap();
+/// ```
+pub struct CompressWriter<W: Write> {
+    inner: Option<W>,
+    encoder: Encoder,
+    chunk_len: usize,
+    buffer: Vec<u8>,
+    compressed: Vec<u8>,
+    decompressed_len: u64,
+}
+
+impl<W: Write> CompressWriter<W> {
+    /// Create a writer compressing to `inner`.
+    ///
+    /// When `options` is `None`, [CompressOptions::for_compressor] is used.
+    /// Seek chunk reset is always enabled, as blocks are compressed
+    /// independently.
+    pub fn new(
+        inner: W,
+        compressor: Compressor,
+        level: CompressionLevel,
+        options: Option<CompressOptions>,
+    ) -> Result<Self, OodleError> {
+        let mut options =
+            options.unwrap_or_else(|| CompressOptions::for_compressor(compressor, level));
+        options.check()?;
+
+        if options.seek_chunk_len == 0 {
+            options.seek_chunk_len = BLOCK_LEN;
+        }
+        options.seek_chunk_reset = true;
+
+        let chunk_len = options.seek_chunk_len as usize;
+
+        Ok(Self {
+            inner: Some(inner),
+            encoder: Encoder::new(compressor, level, Some(options), chunk_len)?,
+            chunk_len,
+            buffer: Vec::with_capacity(chunk_len),
+            compressed: vec![0u8; crate::compressed_buffer_size_needed(compressor, chunk_len)],
+            decompressed_len: 0,
+        })
+    }
+
+    /// Number of bytes written to the compressor so far.
+    pub fn decompressed_len(&self) -> u64 {
+        self.decompressed_len
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Get a mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Compress the buffered block and write it to the inner writer.
+    fn write_block(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let compressed_len = self.encoder.encode(&self.buffer, &mut self.compressed)?;
+        self.inner
+            .as_mut()
+            .unwrap()
+            .write_all(&self.compressed[..compressed_len])?;
+        self.buffer.clear();
+
+        Ok(())
+    }
+
+    /// Compress the remaining data and return the inner writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.write_block()?;
+
+        let mut inner = self.inner.take().unwrap();
+        inner.flush()?;
+
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for CompressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // The full block is only written before taking more data, so that a
+        // failure leaves `buf` unconsumed and the block buffered for a retry.
+        if self.buffer.len() == self.chunk_len {
+            self.write_block()?;
+        }
+
+        let len = buf.len().min(self.chunk_len - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        self.decompressed_len += len as u64;
+
+        Ok(len)
+    }
+
+    /// Flush the inner writer.
+    ///
+    /// The buffered partial block is not compressed, as only the last block
+    /// of the stream may be shorter than `seek_chunk_len`.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for CompressWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_block();
+        }
+    }
+}
+
+/// Default number of decoded seek chunks kept by a [SeekableReader].
+const DEFAULT_CACHE_CAPACITY: usize = 4;
+
+/// Random-access decompressor over compressed data with independent seek
+/// chunks, implementing [Read] and [Seek] over the decompressed positions.
+///
+/// Only the seek chunks touched by a read are decoded, and the most recently
+/// used ones are kept in a small LRU cache. The compressed data must have been
+/// compressed with `seek_chunk_reset`, for example with
+/// [compress_with_seek_table](crate::compress_with_seek_table).
+///
+/// # Example
+///
+/// ```rust
+/// use std::io::{Cursor, Read, Seek, SeekFrom};
+///
+/// let decompressed = include_bytes!("../test_data/decompressed");
+///
+/// let (compressed, seek_table) = oodle_safe::compress_with_seek_table(
+///     oodle_safe::Compressor::Kraken,
+///     decompressed,
+///     oodle_safe::CompressionLevel::Normal,
+///     None,
+/// )
+/// .unwrap_or_else(|err| panic!("compression failed: {}", err));
+///
+/// let mut reader = oodle_safe::SeekableReader::new(Cursor::new(compressed), seek_table)
+///     .unwrap_or_else(|err| panic!("invalid seek table: {}", err));
+///
+/// let mut record = [0u8; 16];
+/// reader.seek(SeekFrom::Start(1000)).unwrap();
+/// reader.read_exact(&mut record).unwrap();
+///
+/// assert_eq!(record, decompressed[1000..1016]);
+/// ```
+pub struct SeekableReader<R: Read + Seek> {
+    inner: R,
+    seek_table: SeekTable,
+    check_crc: CheckCRC,
+
+    /// Position of the compressed data in `inner`.
+    compressed_offset: u64,
+
+    /// Decompressed position of the next byte to return to the reader.
+    pos: u64,
+
+    /// Decoded seek chunks, most recently used first.
+    cache: VecDeque<(usize, Vec<u8>)>,
+    cache_capacity: usize,
+
+    /// Compressed data of the seek chunk being decoded.
+    compressed: Vec<u8>,
+}
+
+impl<R: Read + Seek> SeekableReader<R> {
+    /// Create a reader decompressing the data described by `seek_table` from
+    /// `inner`.
+    ///
+    /// Returns [OodleError::InvalidFormat] if the seek chunks are not
+    /// independent.
+    pub fn new(inner: R, seek_table: SeekTable) -> Result<Self, OodleError> {
+        if !seek_table.is_independent() {
+            return Err(OodleError::InvalidFormat("seek chunks are not independent"));
+        }
+
+        Ok(Self {
+            inner,
+            seek_table,
+            check_crc: CheckCRC::No,
+            compressed_offset: 0,
+            pos: 0,
+            cache: VecDeque::new(),
+            cache_capacity: DEFAULT_CACHE_CAPACITY,
+            compressed: Vec::new(),
+        })
+    }
+
+    /// Whether to check the quantum CRCs of the compressed data.
+    ///
+    /// The CRCs of the seek table, if any, are always checked.
+    pub fn set_check_crc(&mut self, check_crc: CheckCRC) {
+        self.check_crc = check_crc;
+    }
+
+    /// Set the position of the compressed data in the inner reader, when it
+    /// does not start at the beginning of it.
+    pub fn set_compressed_offset(&mut self, offset: u64) {
+        self.compressed_offset = offset;
+    }
+
+    /// Set the number of decoded seek chunks kept in memory, at least one.
+    pub fn set_cache_capacity(&mut self, capacity: usize) {
+        self.cache_capacity = capacity.max(1);
+        self.cache.truncate(self.cache_capacity);
+    }
+
+    /// Seek table of the compressed data.
+    pub fn seek_table(&self) -> &SeekTable {
+        &self.seek_table
+    }
+
+    /// Total size of the decompressed data.
+    pub fn decompressed_len(&self) -> u64 {
+        self.seek_table.decompressed_len()
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Decoded data of the seek chunk `entry`, decoding it if it is not in
+    /// the cache.
+    fn chunk(&mut self, entry: usize) -> std::io::Result<&[u8]> {
+        if let Some(index) = self.cache.iter().position(|(x, _)| *x == entry) {
+            let chunk = self.cache.remove(index).unwrap();
+            self.cache.push_front(chunk);
+            return Ok(&self.cache[0].1);
+        }
+
+        let compressed_range = self.seek_table.compressed_range(entry);
+        let decompressed_range = self.seek_table.decompressed_range(entry);
+
+        self.compressed
+            .resize((compressed_range.end - compressed_range.start) as usize, 0);
+        self.inner.seek(SeekFrom::Start(
+            self.compressed_offset + compressed_range.start,
+        ))?;
+        self.inner.read_exact(&mut self.compressed)?;
+
+        // Reuse the buffer of the least recently used chunk when evicting it.
+        let mut decompressed = if self.cache.len() >= self.cache_capacity {
+            self.cache.pop_back().unwrap().1
+        } else {
+            Vec::new()
+        };
+        decompressed.resize(
+            (decompressed_range.end - decompressed_range.start) as usize,
+            0,
+        );
+
+        crate::decompress(
+            &self.compressed,
+            &mut decompressed,
+            None,
+            Some(self.check_crc),
+            None,
+            None,
+        )?;
+        self.seek_table.check_chunk_crc(entry, &decompressed)?;
+
+        self.cache.push_front((entry, decompressed));
+        Ok(&self.cache[0].1)
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(entry) = self.seek_table.find_seek_entry(self.pos) else {
+            return Ok(0);
+        };
+
+        let offset = (self.pos - self.seek_table.decompressed_range(entry).start) as usize;
+        let chunk = &self.chunk(entry)?[offset..];
+
+        let len = chunk.len().min(buf.len());
+        buf[..len].copy_from_slice(&chunk[..len]);
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(x) => {
+                self.pos = x;
+                return Ok(x);
+            }
+            SeekFrom::End(x) => (self.decompressed_len(), x),
+            SeekFrom::Current(x) => (self.pos, x),
+        };
+
+        match base.checked_add_signed(offset) {
+            Some(x) => {
+                self.pos = x;
+                Ok(x)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        Ok(self.pos)
+    }
+}
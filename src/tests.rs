@@ -22,7 +22,7 @@ fn test_default_compress_options() {
     assert_eq!(options.make_long_range_matcher, true);
     assert_eq!(options.match_table_size_log2, 0);
     assert_eq!(options.jobify, oodle_safe::Jobify::Default);
-    assert_eq!(options.jobify_user_ptr, 0x0 as *mut std::ffi::c_void);
+    assert_eq!(options.job_system, None);
     assert_eq!(options.far_match_min_len, 0);
     assert_eq!(options.far_match_offset_log2, 0);
 }
@@ -47,7 +47,7 @@ fn test_compress_options_validate() {
 #[rustfmt::skip]
 fn test_compress() {
     let decompressed = include_bytes!("../test_data/decompressed");
-    let mut compressed = vec![0u8; decompressed.len() + 8];
+    let mut compressed = vec![0u8; oodle_safe::compressed_buffer_size_needed(oodle_safe::Compressor::Kraken, decompressed.len())];
 
     let compressed_size = oodle_safe::compress(
         oodle_safe::Compressor::Kraken,
@@ -73,7 +73,13 @@ fn test_compress() {
 #[test]
 fn test_compress_with_default_options() {
     let decompressed = include_bytes!("../test_data/decompressed");
-    let mut compressed = vec![0u8; decompressed.len() + 8];
+    let mut compressed = vec![
+        0u8;
+        oodle_safe::compressed_buffer_size_needed(
+            oodle_safe::Compressor::Kraken,
+            decompressed.len()
+        )
+    ];
 
     let compressed_size = oodle_safe::compress(
         oodle_safe::Compressor::Kraken,
@@ -120,3 +126,1791 @@ fn test_decompress() {
     let expected = include_bytes!("../test_data/decompressed");
     assert_eq!(decompressed, expected);
 }
+
+#[test]
+fn test_compress_buffer_too_small() {
+    let decompressed = include_bytes!("../test_data/decompressed");
+    let mut compressed = vec![0u8; decompressed.len()];
+
+    let result = oodle_safe::compress(
+        oodle_safe::Compressor::Kraken,
+        decompressed,
+        &mut compressed,
+        oodle_safe::CompressionLevel::Normal,
+        None,
+        None,
+        None,
+    );
+
+    assert_eq!(
+        result,
+        Err(oodle_safe::OodleError::BufferTooSmall {
+            required: oodle_safe::compressed_buffer_size_needed(
+                oodle_safe::Compressor::Kraken,
+                decompressed.len()
+            ),
+            provided: decompressed.len(),
+        })
+    );
+}
+
+#[test]
+fn test_compress_invalid_options() {
+    let decompressed = include_bytes!("../test_data/decompressed");
+    let mut compressed = vec![
+        0u8;
+        oodle_safe::compressed_buffer_size_needed(
+            oodle_safe::Compressor::Kraken,
+            decompressed.len()
+        )
+    ];
+
+    let options = oodle_safe::CompressOptions {
+        seek_chunk_len: oodle_safe::BLOCK_LEN + 1,
+        ..Default::default()
+    };
+
+    let result = oodle_safe::compress(
+        oodle_safe::Compressor::Kraken,
+        decompressed,
+        &mut compressed,
+        oodle_safe::CompressionLevel::Normal,
+        Some(options),
+        None,
+        None,
+    );
+
+    assert!(matches!(
+        result,
+        Err(oodle_safe::OodleError::InvalidOptions(_))
+    ));
+}
+
+#[test]
+fn test_decompress_invalid_header() {
+    let compressed = [0xffu8; 16];
+    let mut decompressed = vec![0u8; 64];
+
+    let result = oodle_safe::decompress(&compressed, &mut decompressed, None, None, None, None);
+
+    assert_eq!(result, Err(oodle_safe::OodleError::InvalidHeader));
+}
+
+#[test]
+fn test_decompress_corrupt_data() {
+    let compressed = include_bytes!("../test_data/compressed");
+    let decompressed_size = u32::from_le_bytes(compressed[..4].try_into().unwrap()) as usize;
+    let mut decompressed = vec![0u8; decompressed_size];
+
+    // Truncate the compressed data, keeping the chunk header intact.
+    let result = oodle_safe::decompress(
+        &compressed[4..compressed.len() / 2],
+        &mut decompressed,
+        None,
+        None,
+        None,
+        None,
+    );
+
+    assert_eq!(result, Err(oodle_safe::OodleError::CorruptData));
+}
+
+#[test]
+fn test_compress_to_vec() {
+    let decompressed = include_bytes!("../test_data/decompressed");
+
+    let compressed = oodle_safe::compress_to_vec(
+        oodle_safe::Compressor::Kraken,
+        decompressed,
+        oodle_safe::CompressionLevel::Normal,
+        None,
+        None,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let expected = include_bytes!("../test_data/compressed");
+    assert_eq!(compressed, &expected[4..]);
+}
+
+#[test]
+fn test_decompress_to_vec() {
+    let compressed = include_bytes!("../test_data/compressed");
+    let decompressed_size = u32::from_le_bytes(compressed[..4].try_into().unwrap()) as usize;
+
+    let decompressed =
+        oodle_safe::decompress_to_vec(&compressed[4..], decompressed_size, None, None, None)
+            .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    let expected = include_bytes!("../test_data/decompressed");
+    assert_eq!(decompressed, expected);
+}
+
+#[test]
+fn test_compress_options_builder() {
+    let options = oodle_safe::CompressOptions::builder()
+        .seek_chunk_reset(true)
+        .seek_chunk_len(oodle_safe::BLOCK_LEN * 4)
+        .send_quantum_crcs(true)
+        .dictionary_size(1 << 20)
+        .jobify(oodle_safe::Jobify::Disable)
+        .build()
+        .unwrap_or_else(|err| panic!("invalid options: {}", err));
+
+    assert!(options.seek_chunk_reset());
+    assert_eq!(options.seek_chunk_len(), oodle_safe::BLOCK_LEN * 4);
+    assert!(options.send_quantum_crcs());
+    assert_eq!(options.dictionary_size(), 1 << 20);
+    assert_eq!(options.jobify(), oodle_safe::Jobify::Disable);
+}
+
+#[test]
+fn test_compress_options_builder_invalid() {
+    let result = oodle_safe::CompressOptions::builder()
+        .seek_chunk_len(oodle_safe::BLOCK_LEN / 2)
+        .build();
+    assert!(matches!(
+        result,
+        Err(oodle_safe::OodleError::InvalidOptions(_))
+    ));
+
+    let result = oodle_safe::CompressOptions::builder()
+        .max_local_dictionary_size(oodle_safe::LOCALDICTIONARYSIZE_MAX)
+        .build();
+    assert!(matches!(
+        result,
+        Err(oodle_safe::OodleError::InvalidOptions(_))
+    ));
+}
+
+#[test]
+fn test_compress_with_compressor_options() {
+    let decompressed = include_bytes!("../test_data/decompressed");
+
+    let options = oodle_safe::CompressOptions::for_compressor(
+        oodle_safe::Compressor::Kraken,
+        oodle_safe::CompressionLevel::Normal,
+    );
+
+    let compressed = oodle_safe::compress_to_vec(
+        oodle_safe::Compressor::Kraken,
+        decompressed,
+        oodle_safe::CompressionLevel::Normal,
+        Some(options),
+        None,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let expected = include_bytes!("../test_data/compressed");
+    assert_eq!(compressed, &expected[4..]);
+}
+
+#[test]
+fn test_encoder() {
+    let decompressed = include_bytes!("../test_data/decompressed");
+
+    let mut encoder = oodle_safe::Encoder::new(
+        oodle_safe::Compressor::Kraken,
+        oodle_safe::CompressionLevel::Normal,
+        None,
+        decompressed.len(),
+    )
+    .unwrap_or_else(|err| panic!("invalid encoder: {}", err));
+
+    let expected = include_bytes!("../test_data/compressed");
+
+    // The scratch memory is reused across calls.
+    for _ in 0..2 {
+        let compressed = encoder
+            .encode_to_vec(decompressed)
+            .unwrap_or_else(|err| panic!("compression failed: {}", err));
+        assert_eq!(compressed, &expected[4..]);
+    }
+}
+
+#[test]
+fn test_encoder_input_too_large() {
+    let decompressed = include_bytes!("../test_data/decompressed");
+
+    let mut encoder = oodle_safe::Encoder::new(
+        oodle_safe::Compressor::Kraken,
+        oodle_safe::CompressionLevel::Normal,
+        None,
+        decompressed.len() - 1,
+    )
+    .unwrap_or_else(|err| panic!("invalid encoder: {}", err));
+
+    assert_eq!(
+        encoder.encode_to_vec(decompressed),
+        Err(oodle_safe::OodleError::InputTooLarge {
+            max: decompressed.len() - 1,
+            provided: decompressed.len(),
+        })
+    );
+}
+
+#[test]
+fn test_decoder() {
+    let compressed = include_bytes!("../test_data/compressed");
+    let decompressed_size = u32::from_le_bytes(compressed[..4].try_into().unwrap()) as usize;
+
+    let mut decoder = oodle_safe::Decoder::new(decompressed_size);
+
+    // The decoder can be moved to a worker thread.
+    let decompressed = std::thread::spawn(move || {
+        let mut decompressed = vec![0u8; decompressed_size];
+        for _ in 0..2 {
+            decoder
+                .decode(&compressed[4..], &mut decompressed, None, None)
+                .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+        }
+        decompressed
+    })
+    .join()
+    .unwrap();
+
+    let expected = include_bytes!("../test_data/decompressed");
+    assert_eq!(decompressed, expected);
+
+    // The same decoder decodes any compressor.
+    let mut decoder = oodle_safe::Decoder::new(expected.len());
+    for compressor in [
+        oodle_safe::Compressor::Mermaid,
+        oodle_safe::Compressor::Hydra,
+    ] {
+        let compressed = oodle_safe::compress_to_vec(
+            compressor,
+            expected,
+            oodle_safe::CompressionLevel::Fast,
+            None,
+            None,
+            None,
+        )
+        .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+        let mut decompressed = vec![0u8; expected.len()];
+        decoder
+            .decode(&compressed, &mut decompressed, None, None)
+            .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+        assert_eq!(decompressed, expected);
+    }
+}
+
+/// Reader returning its data in small pieces, to exercise partial reads.
+struct SlowReader<'a>(&'a [u8]);
+
+impl std::io::Read for SlowReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.0.len()).min(7);
+        buf[..len].copy_from_slice(&self.0[..len]);
+        self.0 = &self.0[len..];
+        Ok(len)
+    }
+}
+
+/// Compressible data spanning several blocks.
+fn generate_data(len: usize) -> Vec<u8> {
+    let mut state = 0x2545f491u32;
+    (0..len)
+        .map(|i| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            if state.is_multiple_of(4) {
+                state as u8
+            } else {
+                b"oodle"[i % 5]
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_decompress_reader() {
+    use std::io::Read;
+
+    let compressed = include_bytes!("../test_data/compressed");
+    let decompressed_size = u32::from_le_bytes(compressed[..4].try_into().unwrap()) as usize;
+
+    let mut reader = oodle_safe::DecompressReader::new(
+        SlowReader(&compressed[4..]),
+        decompressed_size as u64,
+        decompressed_size,
+    )
+    .unwrap();
+
+    let mut decompressed = Vec::new();
+    reader.read_to_end(&mut decompressed).unwrap();
+
+    let expected = include_bytes!("../test_data/decompressed");
+    assert_eq!(decompressed, expected);
+}
+
+#[test]
+fn test_decompress_reader_sliding_window() {
+    use std::io::Read;
+
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 5 + 1234);
+
+    let options = oodle_safe::CompressOptions::builder()
+        .seek_chunk_reset(true)
+        .seek_chunk_len(oodle_safe::BLOCK_LEN)
+        .build()
+        .unwrap();
+
+    let compressed = oodle_safe::compress_to_vec(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Fast,
+        Some(options),
+        None,
+        None,
+    )
+    .unwrap();
+
+    let mut reader = oodle_safe::DecompressReader::new(
+        compressed.as_slice(),
+        data.len() as u64,
+        oodle_safe::BLOCK_LEN as usize,
+    )
+    .unwrap();
+
+    let mut decompressed = Vec::new();
+    reader.read_to_end(&mut decompressed).unwrap();
+
+    assert_eq!(decompressed, data);
+}
+
+#[test]
+fn test_compress_writer() {
+    use std::io::Write;
+
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 3 + 4321);
+
+    let mut writer = oodle_safe::CompressWriter::new(
+        Vec::new(),
+        oodle_safe::Compressor::Kraken,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("invalid writer: {}", err));
+
+    for chunk in data.chunks(10000) {
+        writer.write_all(chunk).unwrap();
+    }
+
+    assert_eq!(writer.decompressed_len(), data.len() as u64);
+    let compressed = writer.finish().unwrap();
+
+    let decompressed = oodle_safe::decompress_to_vec(&compressed, data.len(), None, None, None)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+    assert_eq!(decompressed, data);
+}
+
+#[test]
+fn test_compress_writer_retry() {
+    use std::io::Write;
+
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 2 + 4321);
+
+    let mut writer = oodle_safe::CompressWriter::new(
+        FailingWriter {
+            data: Vec::new(),
+            fail: true,
+        },
+        oodle_safe::Compressor::Kraken,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("invalid writer: {}", err));
+
+    // The failed write consumes nothing, and is retried.
+    let mut pos = 0;
+    let mut failures = 0;
+    while pos < data.len() {
+        match writer.write(&data[pos..]) {
+            Ok(len) => pos += len,
+            Err(_) => failures += 1,
+        }
+    }
+    assert_eq!(failures, 1);
+    assert_eq!(writer.decompressed_len(), data.len() as u64);
+
+    let compressed = writer.finish().unwrap().data;
+    let decompressed = oodle_safe::decompress_to_vec(&compressed, data.len(), None, None, None)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+    assert_eq!(decompressed, data);
+}
+
+fn compress_generated_data(len: usize) -> (Vec<u8>, Vec<u8>) {
+    let data = generate_data(len);
+
+    let compressed = oodle_safe::compress_to_vec(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+        None,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    (data, compressed)
+}
+
+#[test]
+fn test_decompress_with_callback() {
+    let (data, compressed) = compress_generated_data(oodle_safe::BLOCK_LEN as usize * 4);
+    let mut decompressed = vec![0u8; data.len()];
+
+    let mut calls = Vec::new();
+    oodle_safe::decompress_with_callback(
+        &compressed,
+        &mut decompressed,
+        None,
+        None,
+        None,
+        None,
+        |progress| {
+            calls.push(progress);
+            oodle_safe::CallbackAction::Continue
+        },
+    )
+    .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    assert_eq!(decompressed, data);
+    assert!(!calls.is_empty());
+    assert!(calls
+        .windows(2)
+        .all(|x| x[0].decompressed_done <= x[1].decompressed_done));
+    assert!(calls.iter().all(|x| x.decompressed_len == data.len()));
+}
+
+#[test]
+fn test_decompress_with_callback_cancel() {
+    let (data, compressed) = compress_generated_data(oodle_safe::BLOCK_LEN as usize * 4);
+    let mut decompressed = vec![0u8; data.len()];
+
+    let result = oodle_safe::decompress_with_callback(
+        &compressed,
+        &mut decompressed,
+        None,
+        None,
+        None,
+        None,
+        |_| oodle_safe::CallbackAction::Cancel,
+    );
+
+    assert_eq!(result, Err(oodle_safe::OodleError::Cancelled));
+}
+
+#[test]
+#[should_panic(expected = "callback panic")]
+fn test_decompress_with_callback_panic() {
+    let (data, compressed) = compress_generated_data(oodle_safe::BLOCK_LEN as usize * 4);
+    let mut decompressed = vec![0u8; data.len()];
+
+    let _ = oodle_safe::decompress_with_callback(
+        &compressed,
+        &mut decompressed,
+        None,
+        None,
+        None,
+        None,
+        |_| panic!("callback panic"),
+    );
+}
+
+#[test]
+fn test_seek_table() {
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 5 + 1000);
+
+    let (compressed, seek_table) = oodle_safe::compress_with_seek_table(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    assert_eq!(seek_table.num_seek_chunks(), 6);
+    assert_eq!(seek_table.decompressed_len(), data.len() as u64);
+    assert_eq!(seek_table.compressed_len(), compressed.len() as u64);
+    assert_eq!(
+        seek_table.compressor(),
+        Some(oodle_safe::Compressor::Kraken)
+    );
+    assert!(seek_table.is_independent());
+    assert!(seek_table.has_crcs());
+
+    let pos = oodle_safe::BLOCK_LEN as u64 * 3 + 10;
+    let entry = seek_table.find_seek_entry(pos).unwrap();
+    assert_eq!(entry, 3);
+    assert_eq!(seek_table.find_seek_entry(data.len() as u64), None);
+
+    let decompressed_range = seek_table.decompressed_range(entry);
+    let compressed_range = seek_table.compressed_range(entry);
+    let chunk = &compressed[compressed_range.start as usize..compressed_range.end as usize];
+    let mut decompressed = vec![0u8; (decompressed_range.end - decompressed_range.start) as usize];
+
+    oodle_safe::decompress(chunk, &mut decompressed, None, None, None, None)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    assert_eq!(
+        decompressed,
+        &data[decompressed_range.start as usize..decompressed_range.end as usize]
+    );
+
+    seek_table.check_chunk_crc(entry, &decompressed).unwrap();
+    seek_table.check_crcs(&data).unwrap();
+
+    let mut corrupted = data.clone();
+    corrupted[pos as usize] ^= 0xff;
+    assert_eq!(
+        seek_table.check_crcs(&corrupted),
+        Err(oodle_safe::OodleError::CrcMismatch)
+    );
+}
+
+#[test]
+fn test_seek_table_bytes() {
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 3);
+
+    let (_, seek_table) = oodle_safe::compress_with_seek_table(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let bytes = seek_table.to_bytes();
+    assert_eq!(oodle_safe::SeekTable::from_bytes(&bytes), Ok(seek_table));
+
+    assert!(matches!(
+        oodle_safe::SeekTable::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(oodle_safe::OodleError::InvalidFormat(_))
+    ));
+
+    // A seek chunk length which would be negative for Oodle is rejected, even
+    // with a matching number of seek chunks.
+    let mut forged = bytes.clone();
+    forged[10..18].copy_from_slice(&(2 * 0x8000_0000u64 + 1).to_le_bytes());
+    forged[18..22].copy_from_slice(&0x8000_0000u32.to_le_bytes());
+    assert!(matches!(
+        oodle_safe::SeekTable::from_bytes(&forged),
+        Err(oodle_safe::OodleError::InvalidFormat(_))
+    ));
+}
+
+#[test]
+fn test_seekable_reader() {
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 5 + 1234);
+
+    let (compressed, seek_table) = oodle_safe::compress_with_seek_table(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    // The compressed data does not have to start at the beginning of the reader.
+    let mut blob = vec![0xaa; 100];
+    blob.extend_from_slice(&compressed);
+
+    let mut reader = oodle_safe::SeekableReader::new(Cursor::new(blob), seek_table).unwrap();
+    reader.set_compressed_offset(100);
+    reader.set_cache_capacity(2);
+
+    // Read across the boundary of two seek chunks.
+    let pos = oodle_safe::BLOCK_LEN as usize * 3 - 10;
+    let mut record = [0u8; 20];
+    reader.seek(SeekFrom::Start(pos as u64)).unwrap();
+    reader.read_exact(&mut record).unwrap();
+    assert_eq!(record, data[pos..pos + 20]);
+
+    reader.seek(SeekFrom::Current(-40)).unwrap();
+    reader.read_exact(&mut record).unwrap();
+    assert_eq!(record, data[pos - 20..pos]);
+
+    reader.seek(SeekFrom::End(-20)).unwrap();
+    reader.read_exact(&mut record).unwrap();
+    assert_eq!(record, data[data.len() - 20..]);
+    assert_eq!(reader.read(&mut record).unwrap(), 0);
+
+    assert!(reader
+        .seek(SeekFrom::Current(-(data.len() as i64) - 1))
+        .is_err());
+
+    let mut decompressed = Vec::new();
+    reader.rewind().unwrap();
+    reader.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, data);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_compress_parallel() {
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 9 + 1234);
+
+    let compressed = oodle_safe::compress_parallel(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let mut decompressed = vec![0u8; data.len()];
+    oodle_safe::decompress(&compressed, &mut decompressed, None, None, None, None)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    assert_eq!(decompressed, data);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_decompress_parallel() {
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 9 + 1234);
+
+    let compressed = oodle_safe::compress_parallel(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let mut decompressed = vec![0u8; data.len()];
+    let len = oodle_safe::decompress_parallel(&compressed, &mut decompressed, None)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    assert_eq!(len, data.len());
+    assert_eq!(decompressed, data);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_decompress_parallel_dependent_chunks() {
+    let (data, compressed) = compress_generated_data(oodle_safe::BLOCK_LEN as usize * 5 + 1234);
+
+    let mut decompressed = vec![0u8; data.len()];
+    oodle_safe::decompress_parallel(&compressed, &mut decompressed, None)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    assert_eq!(decompressed, data);
+}
+
+#[test]
+fn test_decompress_threaded() {
+    for compressor in [
+        oodle_safe::Compressor::Kraken,
+        oodle_safe::Compressor::Mermaid,
+    ] {
+        let data = generate_data(oodle_safe::BLOCK_LEN as usize * 5 + 1234);
+
+        let compressed = oodle_safe::compress_to_vec(
+            compressor,
+            &data,
+            oodle_safe::CompressionLevel::Normal,
+            None,
+            None,
+            None,
+        )
+        .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+        let mut unthreaded = vec![0u8; data.len()];
+        let unthreaded_len =
+            oodle_safe::decompress(&compressed, &mut unthreaded, None, None, None, None)
+                .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+        let mut threaded = vec![0u8; data.len()];
+        let threaded_len = oodle_safe::decompress_threaded(&compressed, &mut threaded, None)
+            .unwrap_or_else(|err| panic!("threaded decompression failed: {}", err));
+
+        assert_eq!(threaded_len, unthreaded_len);
+        assert_eq!(threaded, unthreaded);
+        assert_eq!(threaded, data);
+    }
+}
+
+#[test]
+fn test_decompress_threaded_corrupt_data() {
+    let (data, mut compressed) = compress_generated_data(oodle_safe::BLOCK_LEN as usize * 3);
+    let len = compressed.len();
+    compressed[len / 2..].iter_mut().for_each(|x| *x = !*x);
+
+    let mut unthreaded = vec![0u8; data.len()];
+    let unthreaded_result =
+        oodle_safe::decompress(&compressed, &mut unthreaded, None, None, None, None);
+
+    let mut threaded = vec![0u8; data.len()];
+    let threaded_result = oodle_safe::decompress_threaded(&compressed, &mut threaded, None);
+
+    assert!(threaded_result.is_err());
+    assert_eq!(threaded_result, unthreaded_result);
+}
+
+#[test]
+fn test_decode_phased() {
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 5 + 1234);
+
+    let compressed = oodle_safe::compress_to_vec(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Normal,
+        None,
+        None,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let blocks = oodle_safe::SeekTable::new(&compressed, data.len() as u64, oodle_safe::BLOCK_LEN)
+        .unwrap_or_else(|err| panic!("seek table failed: {}", err));
+    assert!(blocks.num_seek_chunks() > 1);
+
+    let mut decompressed = vec![0u8; data.len()];
+    let output = crate::threaded::Output {
+        ptr: decompressed.as_mut_ptr(),
+        len: decompressed.len(),
+    };
+
+    assert!(crate::threaded::decode_phased(
+        &compressed,
+        output,
+        &blocks,
+        oodle_safe::CheckCRC::No,
+    ));
+    assert_eq!(decompressed, data);
+}
+
+#[test]
+fn test_compress_with_job_system() {
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 4 + 1234);
+    let job_system = oodle_safe::JobSystemHandle::new(oodle_safe::ThreadJobSystem::new());
+
+    let options = oodle_safe::CompressOptions::builder()
+        .jobify(oodle_safe::Jobify::Aggressive)
+        .job_system(job_system)
+        .build()
+        .unwrap();
+
+    assert_eq!(options.job_system(), Some(job_system));
+
+    let compressed = oodle_safe::compress_to_vec(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Optimal1,
+        Some(options),
+        None,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let mut decompressed = vec![0u8; data.len()];
+    oodle_safe::decompress(&compressed, &mut decompressed, None, None, None, None)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    assert_eq!(decompressed, data);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_compress_with_rayon_job_system() {
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 4 + 1234);
+    let job_system = oodle_safe::JobSystemHandle::new(oodle_safe::RayonJobSystem::new());
+
+    let options = oodle_safe::CompressOptions::builder()
+        .jobify(oodle_safe::Jobify::Normal)
+        .job_system(job_system)
+        .build()
+        .unwrap();
+
+    let compressed = oodle_safe::compress_to_vec(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Optimal1,
+        Some(options),
+        None,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let mut decompressed = vec![0u8; data.len()];
+    oodle_safe::decompress(&compressed, &mut decompressed, None, None, None, None)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    assert_eq!(decompressed, data);
+}
+
+#[cfg(any(feature = "log", feature = "tracing"))]
+#[test]
+fn test_install_logger() {
+    oodle_safe::install_logger(oodle_safe::AssertionAction::Error);
+    oodle_safe::install_logger(oodle_safe::AssertionAction::Log);
+
+    let (data, compressed) = compress_generated_data(oodle_safe::BLOCK_LEN as usize);
+
+    let mut decompressed = vec![0u8; data.len()];
+    oodle_safe::decompress(
+        &compressed,
+        &mut decompressed,
+        None,
+        None,
+        Some(oodle_safe::Verbosity::Lots),
+        None,
+    )
+    .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    assert_eq!(decompressed, data);
+    assert_eq!(oodle_safe::take_assertion(), None);
+}
+
+#[cfg(any(feature = "log", feature = "tracing"))]
+#[test]
+fn test_assertion_cleared_between_calls() {
+    oodle_safe::install_logger(oodle_safe::AssertionAction::Error);
+    let (data, compressed) = compress_generated_data(oodle_safe::BLOCK_LEN as usize);
+
+    // Release builds of Oodle never assert, so raise one as a call would.
+    unsafe {
+        crate::logging::display_assertion(
+            c"oodle.c".as_ptr(),
+            1,
+            std::ptr::null(),
+            c"simulated".as_ptr(),
+        );
+    }
+
+    let mut decompressed = vec![0u8; data.len()];
+    oodle_safe::decompress(&compressed, &mut decompressed, None, None, None, None)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    assert_eq!(decompressed, data);
+    assert_eq!(oodle_safe::take_assertion(), None);
+
+    oodle_safe::install_logger(oodle_safe::AssertionAction::Log);
+}
+
+#[test]
+fn test_inspect() {
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize * 3 + 1234);
+
+    let options = oodle_safe::CompressOptions::builder()
+        .seek_chunk_reset(true)
+        .seek_chunk_len(oodle_safe::BLOCK_LEN)
+        .send_quantum_crcs(true)
+        .build()
+        .unwrap();
+
+    let compressed = oodle_safe::compress_to_vec(
+        oodle_safe::Compressor::Kraken,
+        &data,
+        oodle_safe::CompressionLevel::Fast,
+        Some(options),
+        None,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let info =
+        oodle_safe::inspect(&compressed).unwrap_or_else(|err| panic!("inspection failed: {}", err));
+
+    assert_eq!(info.chunks.len(), 4);
+    assert_eq!(info.compressor, Some(oodle_safe::Compressor::Kraken));
+    assert_eq!(info.compressed_len(), compressed.len());
+    assert!(info.max_decompressed_len() >= data.len());
+    assert!(info.max_decompressed_len() - data.len() < oodle_safe::BLOCK_LEN as usize);
+    assert!(info.is_independent());
+    assert!(info.has_crcs());
+
+    for chunk in &info.chunks[..3] {
+        assert_eq!(chunk.decompressed_len, oodle_safe::BLOCK_LEN as usize);
+    }
+}
+
+#[test]
+fn test_inspect_invalid_header() {
+    assert_eq!(
+        oodle_safe::inspect(&[0u8; 64]),
+        Err(oodle_safe::OodleError::InvalidHeader)
+    );
+}
+
+#[test]
+fn test_enum_raw_round_trip() {
+    for compressor in oodle_safe::Compressor::all() {
+        let raw: oodle_sys::OodleLZ_Compressor = compressor.into();
+        assert_eq!(oodle_safe::Compressor::try_from(raw), Ok(compressor));
+    }
+
+    for level in oodle_safe::CompressionLevel::all() {
+        let raw: oodle_sys::OodleLZ_CompressionLevel = level.into();
+        assert_eq!(oodle_safe::CompressionLevel::try_from(raw), Ok(level));
+    }
+
+    for jobify in oodle_safe::Jobify::all() {
+        let raw: oodle_sys::OodleLZ_Jobify = jobify.into();
+        assert_eq!(oodle_safe::Jobify::try_from(raw), Ok(jobify));
+    }
+
+    for thread_phase in oodle_safe::DecodeThreadPhase::all() {
+        let raw: oodle_sys::OodleLZ_Decode_ThreadPhase = thread_phase.into();
+        assert_eq!(
+            oodle_safe::DecodeThreadPhase::try_from(raw),
+            Ok(thread_phase)
+        );
+    }
+
+    assert_eq!(
+        oodle_safe::Compressor::try_from(oodle_sys::OodleLZ_Compressor_OodleLZ_Compressor_Invalid),
+        Err(oodle_safe::OodleError::UnknownValue("compressor"))
+    );
+    assert!(oodle_safe::Profile::try_from(42).is_err());
+    assert!(oodle_safe::Jobify::try_from(42).is_err());
+}
+
+#[test]
+fn test_enum_name_round_trip() {
+    for compressor in oodle_safe::Compressor::all() {
+        let name = compressor.to_string();
+        assert!(!name.is_empty());
+        assert_eq!(name.parse(), Ok(compressor));
+        assert_eq!(name.to_lowercase().parse(), Ok(compressor));
+    }
+
+    for level in oodle_safe::CompressionLevel::all() {
+        assert_eq!(level.to_string().parse(), Ok(level));
+    }
+
+    for verbosity in oodle_safe::Verbosity::all() {
+        assert_eq!(verbosity.to_string().parse(), Ok(verbosity));
+    }
+
+    assert_eq!("max".parse(), Ok(oodle_safe::CompressionLevel::Optimal5));
+    assert_eq!(
+        "Snappy".parse::<oodle_safe::Compressor>(),
+        Err(oodle_safe::OodleError::UnknownValue("compressor"))
+    );
+}
+
+#[test]
+fn test_compression_level_ordering() {
+    let levels: Vec<_> = oodle_safe::CompressionLevel::all().collect();
+    assert!(levels.windows(2).all(|x| x[0] < x[1]));
+
+    assert_eq!(
+        oodle_safe::CompressionLevel::Min,
+        oodle_safe::CompressionLevel::HyperFast4
+    );
+    assert_eq!(
+        oodle_safe::CompressionLevel::Max,
+        *levels.iter().max().unwrap()
+    );
+    assert!(oodle_safe::CompressionLevel::Fast < oodle_safe::CompressionLevel::Optimal);
+}
+
+#[test]
+fn test_frame() {
+    let data = generate_data(oodle_safe::BLOCK_LEN as usize + 1234);
+
+    let options = oodle_safe::CompressOptions::builder()
+        .send_quantum_crcs(true)
+        .build()
+        .unwrap();
+
+    let framed = oodle_safe::frame::compress_framed(
+        oodle_safe::Compressor::Mermaid,
+        &data,
+        oodle_safe::CompressionLevel::Fast,
+        Some(options),
+        true,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let header = oodle_safe::frame::FrameHeader::parse(&framed).unwrap();
+    assert_eq!(header.compressor, oodle_safe::Compressor::Mermaid);
+    assert_eq!(header.decompressed_len, data.len() as u64);
+    assert!(header.quantum_crcs);
+    assert!(header.checksum.is_some());
+    assert_eq!(header.to_bytes(), framed[..header.encoded_len()]);
+
+    let decompressed = oodle_safe::frame::decompress_framed(&framed)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+    assert_eq!(decompressed, data);
+
+    // A checksum of other data is reported as a mismatch.
+    let mut framed = framed;
+    framed[16..20].copy_from_slice(&0u32.to_le_bytes());
+    assert_eq!(
+        oodle_safe::frame::decompress_framed(&framed),
+        Err(oodle_safe::OodleError::CrcMismatch)
+    );
+
+    assert!(matches!(
+        oodle_safe::frame::decompress_framed(b"OODX"),
+        Err(oodle_safe::OodleError::InvalidFormat(_))
+    ));
+
+    // A crafted length is rejected before allocating it.
+    let mut header = header;
+    header.decompressed_len = u64::MAX;
+    header.checksum = None;
+    assert!(matches!(
+        oodle_safe::frame::decompress_framed(&header.to_bytes()),
+        Err(oodle_safe::OodleError::InvalidFormat(_))
+    ));
+}
+
+#[test]
+fn test_frame_legacy() {
+    use std::io::Read;
+
+    let compressed = include_bytes!("../test_data/compressed");
+    let expected = include_bytes!("../test_data/decompressed");
+
+    let decompressed = oodle_safe::frame::decompress_legacy(compressed)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+    assert_eq!(decompressed, expected);
+
+    let mut reader = oodle_safe::frame::legacy_reader(compressed.as_slice()).unwrap();
+    let mut decompressed = Vec::new();
+    reader.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, expected);
+}
+
+#[test]
+fn test_container() {
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+    let data = generate_data(3 * oodle_safe::BLOCK_LEN as usize + 1234);
+
+    let mut writer = oodle_safe::container::ContainerWriter::new(
+        Vec::new(),
+        oodle_safe::Compressor::Kraken,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+        Some(oodle_safe::BLOCK_LEN),
+    )
+    .unwrap_or_else(|err| panic!("invalid writer: {}", err));
+
+    writer.write_all(&data).unwrap();
+    assert_eq!(writer.decompressed_len(), data.len() as u64);
+    let container = writer.finish().unwrap();
+
+    let mut reader = oodle_safe::container::ContainerReader::new(Cursor::new(&container))
+        .unwrap_or_else(|err| panic!("invalid container: {}", err));
+    assert_eq!(reader.compressor(), oodle_safe::Compressor::Kraken);
+    assert_eq!(reader.decompressed_len(), data.len() as u64);
+    assert_eq!(reader.blocks().len(), 4);
+    assert_eq!(reader.blocks()[3].decompressed_len, 1234);
+
+    let mut decompressed = Vec::new();
+    reader.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, data);
+
+    let pos = 2 * oodle_safe::BLOCK_LEN as usize - 8;
+    let mut record = [0u8; 16];
+    reader.seek(SeekFrom::Start(pos as u64)).unwrap();
+    reader.read_exact(&mut record).unwrap();
+    assert_eq!(record, data[pos..pos + 16]);
+
+    // A truncated container is rejected when reading its index.
+    let err =
+        oodle_safe::container::ContainerReader::new(Cursor::new(&container[..container.len() - 1]))
+            .err()
+            .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // A forged block size is rejected before allocating it.
+    let mut writer = oodle_safe::container::ContainerWriter::new(
+        Vec::new(),
+        oodle_safe::Compressor::Kraken,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+        None,
+    )
+    .unwrap();
+    writer.write_all(&data[..1234]).unwrap();
+    let mut forged = writer.finish().unwrap();
+
+    let index_pos = forged.len() - 12 - 8;
+    forged[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    forged[index_pos..index_pos + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let err = oodle_safe::container::ContainerReader::new(Cursor::new(&forged))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // Compressed blocks must fit in the u32 sizes of the index.
+    let err = oodle_safe::container::ContainerWriter::new(
+        Vec::new(),
+        oodle_safe::Compressor::Kraken,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+        Some(u32::MAX),
+    )
+    .err()
+    .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+/// Writer failing once when `fail` is set.
+struct FailingWriter {
+    data: Vec<u8>,
+    fail: bool,
+}
+
+impl std::io::Write for FailingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if std::mem::take(&mut self.fail) {
+            return Err(std::io::ErrorKind::Other.into());
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_container_write_retry() {
+    use std::io::{Cursor, Read, Write};
+
+    let data = generate_data(2 * oodle_safe::BLOCK_LEN as usize + 1234);
+
+    let mut writer = oodle_safe::container::ContainerWriter::new(
+        FailingWriter {
+            data: Vec::new(),
+            fail: false,
+        },
+        oodle_safe::Compressor::Kraken,
+        oodle_safe::CompressionLevel::Fast,
+        None,
+        Some(oodle_safe::BLOCK_LEN),
+    )
+    .unwrap_or_else(|err| panic!("invalid writer: {}", err));
+    writer.get_mut().fail = true;
+
+    // The failed write consumes nothing, and is retried.
+    let mut pos = 0;
+    let mut failures = 0;
+    while pos < data.len() {
+        match writer.write(&data[pos..]) {
+            Ok(len) => pos += len,
+            Err(_) => failures += 1,
+        }
+    }
+    assert_eq!(failures, 1);
+
+    let container = writer.finish().unwrap().data;
+    let mut reader = oodle_safe::container::ContainerReader::new(Cursor::new(&container))
+        .unwrap_or_else(|err| panic!("invalid container: {}", err));
+
+    let mut decompressed = Vec::new();
+    reader.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, data);
+}
+
+#[test]
+fn test_archive() {
+    use std::io::Cursor;
+
+    let data = generate_data(100_000);
+    let decompressed = include_bytes!("../test_data/decompressed");
+
+    let build = |paths: &[&str]| {
+        let mut builder = oodle_safe::archive::ArchiveBuilder::new();
+        for path in paths {
+            let (content, compressor) = match *path {
+                "b/decompressed" => (decompressed.as_slice(), oodle_safe::Compressor::Mermaid),
+                _ => (data.as_slice(), oodle_safe::Compressor::Kraken),
+            };
+            builder
+                .insert(
+                    path,
+                    content,
+                    compressor,
+                    oodle_safe::CompressionLevel::Fast,
+                )
+                .unwrap_or_else(|err| panic!("compression failed: {}", err));
+        }
+
+        let mut archive = Vec::new();
+        builder.write(&mut archive).unwrap();
+        archive
+    };
+
+    // The output does not depend on the insertion order.
+    let archive = build(&["a/data", "c/copy", "b/decompressed"]);
+    assert_eq!(archive, build(&["b/decompressed", "a/data", "c/copy"]));
+
+    let mut reader = oodle_safe::archive::ArchiveReader::new(Cursor::new(&archive))
+        .unwrap_or_else(|err| panic!("invalid archive: {}", err));
+
+    let paths: Vec<_> = reader.entries().iter().map(|x| x.path.as_str()).collect();
+    assert_eq!(paths, ["a/data", "b/decompressed", "c/copy"]);
+
+    // Identical contents are stored once.
+    let a = reader.entry("a/data").unwrap().clone();
+    let c = reader.entry("c/copy").unwrap().clone();
+    assert_eq!(a.compressed_offset, c.compressed_offset);
+    assert_eq!(
+        reader.entry("b/decompressed").unwrap().compressor,
+        oodle_safe::Compressor::Mermaid
+    );
+
+    assert_eq!(reader.read("a/data").unwrap(), data);
+    assert_eq!(reader.read("c/copy").unwrap(), data);
+    assert_eq!(reader.read("b/decompressed").unwrap(), decompressed);
+    assert_eq!(
+        reader.read("missing").unwrap_err().kind(),
+        std::io::ErrorKind::NotFound
+    );
+
+    // Replacing an entry keeps the compressed data of the other ones.
+    let mut builder = oodle_safe::archive::ArchiveBuilder::from_reader(&mut reader).unwrap();
+    builder
+        .insert(
+            "c/copy",
+            b"replaced",
+            oodle_safe::Compressor::Selkie,
+            oodle_safe::CompressionLevel::Normal,
+        )
+        .unwrap();
+    assert!(builder.remove("b/decompressed"));
+
+    let mut updated = Vec::new();
+    builder.write(&mut updated).unwrap();
+
+    let mut reader = oodle_safe::archive::ArchiveReader::new(Cursor::new(&updated)).unwrap();
+    assert_eq!(reader.entries().len(), 2);
+    assert_eq!(reader.read_compressed("a/data").unwrap(), {
+        let range = a.compressed_offset as usize..(a.compressed_offset + a.compressed_len) as usize;
+        archive[range].to_vec()
+    });
+    assert_eq!(reader.read("c/copy").unwrap(), b"replaced");
+
+    // Corrupted data is reported by the checksum.
+    let mut corrupted = updated.clone();
+    let entry = reader.entry("c/copy").unwrap();
+    let checksum_pos = corrupted.len() - 16 - 4;
+    assert_eq!(entry.path, "c/copy");
+    corrupted[checksum_pos] ^= 0xFF;
+
+    let mut reader = oodle_safe::archive::ArchiveReader::new(Cursor::new(&corrupted)).unwrap();
+    assert_eq!(
+        reader.read("c/copy").unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+
+    // A crafted decompressed size is rejected with the directory.
+    let mut corrupted = updated.clone();
+    let len_pos = corrupted.len() - 16 - 4 - 3 * 8;
+    corrupted[len_pos..len_pos + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    let err = oodle_safe::archive::ArchiveReader::new(Cursor::new(&corrupted))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // Identical contents compressed differently are also stored once.
+    let mut builder = oodle_safe::archive::ArchiveBuilder::new();
+    for (path, compressor) in [
+        ("mermaid", oodle_safe::Compressor::Mermaid),
+        ("kraken", oodle_safe::Compressor::Kraken),
+    ] {
+        builder
+            .insert(path, &data, compressor, oodle_safe::CompressionLevel::Fast)
+            .unwrap();
+    }
+
+    let mut archive = Vec::new();
+    builder.write(&mut archive).unwrap();
+
+    let mut reader = oodle_safe::archive::ArchiveReader::new(Cursor::new(&archive)).unwrap();
+    let kraken = reader.entry("kraken").unwrap().clone();
+    let mut mermaid = reader.entry("mermaid").unwrap().clone();
+    mermaid.path = kraken.path.clone();
+    assert_eq!(mermaid, kraken);
+    assert_eq!(kraken.compressor, oodle_safe::Compressor::Kraken);
+    assert_eq!(reader.read("mermaid").unwrap(), data);
+}
+
+#[cfg(feature = "unreal")]
+fn write_fstring(out: &mut Vec<u8>, string: &str) {
+    out.extend_from_slice(&(string.len() as i32 + 1).to_le_bytes());
+    out.extend_from_slice(string.as_bytes());
+    out.push(0);
+}
+
+/// Serialize a pak entry record, with block ranges as they are stored.
+#[cfg(feature = "unreal")]
+fn write_pak_record(
+    out: &mut Vec<u8>,
+    version: u32,
+    offset: u64,
+    compressed_len: u64,
+    decompressed_len: u64,
+    blocks: Option<&[(u64, u64)]>,
+) {
+    out.extend_from_slice(&offset.to_le_bytes());
+    out.extend_from_slice(&compressed_len.to_le_bytes());
+    out.extend_from_slice(&decompressed_len.to_le_bytes());
+
+    // Oodle is the custom compression flag before version 8, and the first
+    // compression method name since.
+    let method: u32 = match (blocks, version) {
+        (None, _) => 0,
+        (Some(_), 0..=7) => 0x04,
+        (Some(_), _) => 1,
+    };
+    out.extend_from_slice(&method.to_le_bytes());
+
+    if version < 2 {
+        out.extend_from_slice(&0u64.to_le_bytes());
+    }
+    out.extend_from_slice(&[0u8; 20]);
+
+    if version >= 3 {
+        if let Some(blocks) = blocks {
+            out.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+            for (start, end) in blocks {
+                out.extend_from_slice(&start.to_le_bytes());
+                out.extend_from_slice(&end.to_le_bytes());
+            }
+        }
+        out.push(0);
+        out.extend_from_slice(&0x10000u32.to_le_bytes());
+    }
+}
+
+/// Build a pak of the given version, whose compressed files are split into
+/// Oodle Kraken blocks of 64 KiB.
+#[cfg(feature = "unreal")]
+fn build_pak(version: u32, names: usize, files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 0x10000;
+
+    let mut pak = Vec::new();
+    let mut records = Vec::new();
+
+    for (_, data, compressed) in files {
+        let offset = pak.len() as u64;
+
+        let Some(blocks) = compressed.then(|| {
+            data.chunks(BLOCK_SIZE)
+                .map(|chunk| {
+                    oodle_safe::compress_to_vec(
+                        oodle_safe::Compressor::Kraken,
+                        chunk,
+                        oodle_safe::CompressionLevel::Fast,
+                        None,
+                        None,
+                        None,
+                    )
+                    .unwrap()
+                })
+                .collect::<Vec<_>>()
+        }) else {
+            write_pak_record(
+                &mut pak,
+                version,
+                offset,
+                data.len() as u64,
+                data.len() as u64,
+                None,
+            );
+            pak.extend_from_slice(data);
+            records.push((offset, data.len() as u64, None));
+            continue;
+        };
+
+        let mut record = Vec::new();
+        let placeholder = vec![(0, 0); blocks.len()];
+        write_pak_record(&mut record, version, 0, 0, 0, Some(&placeholder));
+
+        // Block offsets are relative to the record since version 5.
+        let base = if version >= 5 { 0 } else { offset };
+        let mut start = base + record.len() as u64;
+        let ranges: Vec<_> = blocks
+            .iter()
+            .map(|block| {
+                start += block.len() as u64;
+                (start - block.len() as u64, start)
+            })
+            .collect();
+
+        let compressed_len = blocks.iter().map(|x| x.len() as u64).sum::<u64>();
+        write_pak_record(
+            &mut pak,
+            version,
+            0,
+            compressed_len,
+            data.len() as u64,
+            Some(&ranges),
+        );
+        for block in &blocks {
+            pak.extend_from_slice(block);
+        }
+
+        let sizes: Vec<_> = blocks.iter().map(|x| x.len() as u32).collect();
+        records.push((offset, compressed_len, Some((ranges, sizes))));
+    }
+
+    let mut index = Vec::new();
+    write_fstring(&mut index, "../../../");
+
+    if version < 10 {
+        index.extend_from_slice(&(files.len() as u32).to_le_bytes());
+        for ((path, data, _), (offset, compressed_len, blocks)) in files.iter().zip(&records) {
+            write_fstring(&mut index, path);
+            write_pak_record(
+                &mut index,
+                version,
+                *offset,
+                *compressed_len,
+                data.len() as u64,
+                blocks.as_ref().map(|(ranges, _)| ranges.as_slice()),
+            );
+        }
+    } else {
+        // Compressed files are bit-packed, and uncompressed ones are kept as
+        // full records.
+        let mut encoded = Vec::new();
+        let mut full = Vec::new();
+        let mut directory = Vec::new();
+        directory.extend_from_slice(&(files.len() as u32).to_le_bytes());
+
+        for ((path, data, _), (offset, compressed_len, blocks)) in files.iter().zip(&records) {
+            let location = match blocks {
+                Some((_, sizes)) => {
+                    let location = encoded.len() as i32;
+                    let bits = (BLOCK_SIZE as u32 >> 11)
+                        | (sizes.len() as u32) << 6
+                        | 1 << 23
+                        | 1 << 29
+                        | 1 << 30
+                        | 1 << 31;
+                    encoded.extend_from_slice(&bits.to_le_bytes());
+                    encoded.extend_from_slice(&(*offset as u32).to_le_bytes());
+                    encoded.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                    encoded.extend_from_slice(&(*compressed_len as u32).to_le_bytes());
+                    if sizes.len() > 1 {
+                        for size in sizes {
+                            encoded.extend_from_slice(&size.to_le_bytes());
+                        }
+                    }
+                    location
+                }
+                None => {
+                    let index = full.len() as i32;
+                    full.push((*offset, *compressed_len, data.len() as u64));
+                    -(index + 1)
+                }
+            };
+
+            let (directory_name, file_name) = path.rsplit_once('/').unwrap();
+            write_fstring(&mut directory, &format!("/{}/", directory_name));
+            directory.extend_from_slice(&1u32.to_le_bytes());
+            write_fstring(&mut directory, file_name);
+            directory.extend_from_slice(&location.to_le_bytes());
+        }
+
+        let directory_offset = pak.len() as u64;
+        pak.extend_from_slice(&directory);
+
+        index.extend_from_slice(&(files.len() as u32).to_le_bytes());
+        index.extend_from_slice(&0u64.to_le_bytes());
+        index.extend_from_slice(&0u32.to_le_bytes());
+        index.extend_from_slice(&1u32.to_le_bytes());
+        index.extend_from_slice(&directory_offset.to_le_bytes());
+        index.extend_from_slice(&(directory.len() as u64).to_le_bytes());
+        index.extend_from_slice(&[0u8; 20]);
+        index.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+        index.extend_from_slice(&encoded);
+        index.extend_from_slice(&(full.len() as u32).to_le_bytes());
+        for (offset, compressed_len, decompressed_len) in full {
+            write_pak_record(
+                &mut index,
+                version,
+                offset,
+                compressed_len,
+                decompressed_len,
+                None,
+            );
+        }
+    }
+
+    let index_offset = pak.len() as u64;
+    pak.extend_from_slice(&index);
+
+    if version >= 7 {
+        pak.extend_from_slice(&[0u8; 16]);
+    }
+    if version >= 4 {
+        pak.push(0);
+    }
+    pak.extend_from_slice(&oodle_safe::unreal::pak::MAGIC.to_le_bytes());
+    pak.extend_from_slice(&version.to_le_bytes());
+    pak.extend_from_slice(&index_offset.to_le_bytes());
+    pak.extend_from_slice(&(index.len() as u64).to_le_bytes());
+    pak.extend_from_slice(&[0u8; 20]);
+    if version == 9 {
+        pak.push(0);
+    }
+    if version >= 8 {
+        let mut names = vec![0u8; names * 32];
+        names[..5].copy_from_slice(b"Oodle");
+        pak.extend_from_slice(&names);
+    }
+
+    pak
+}
+
+#[cfg(feature = "unreal")]
+#[test]
+fn test_unreal_pak() {
+    use std::io::Cursor;
+
+    let data = generate_data(150_000);
+    let decompressed = include_bytes!("../test_data/decompressed");
+    let files: [(&str, &[u8], bool); 3] = [
+        ("Game/Content/data.uasset", &data, true),
+        ("Game/Config/readme.txt", b"synthetic pak", false),
+        ("Engine/Content/decompressed.uexp", decompressed, true),
+    ];
+
+    // Version 8 has 4 compression method names before UE 4.23, and 5 after.
+    for (version, names) in [
+        (1, 0),
+        (3, 0),
+        (4, 0),
+        (5, 0),
+        (7, 0),
+        (8, 4),
+        (8, 5),
+        (9, 5),
+        (10, 5),
+        (11, 5),
+    ] {
+        let pak = build_pak(version, names, &files);
+
+        let mut reader = oodle_safe::unreal::pak::PakReader::new(Cursor::new(&pak))
+            .unwrap_or_else(|err| panic!("invalid pak version {}: {}", version, err));
+        assert_eq!(reader.version(), version);
+        assert_eq!(reader.mount_point(), "../../../");
+        assert_eq!(reader.entries().len(), files.len());
+
+        for (path, content, compressed) in files {
+            let entry = reader.entry(path).unwrap();
+            if version >= 3 && compressed {
+                assert_eq!(entry.compression_method.as_deref(), Some("Oodle"));
+            }
+
+            // Compression blocks are only stored since version 3.
+            if version >= 3 || !compressed {
+                let output = reader
+                    .read(path)
+                    .unwrap_or_else(|err| panic!("{} in pak version {}: {}", path, version, err));
+                assert_eq!(output, content);
+            }
+        }
+
+        let err = reader.read("Game/missing").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        assert!(
+            oodle_safe::unreal::pak::PakReader::new(Cursor::new(&pak[..pak.len() - 1])).is_err()
+        );
+    }
+
+    // An offset overflowing with the record length is rejected.
+    let mut pak = build_pak(8, 5, &files);
+    let path = b"Game/Config/readme.txt\0";
+    let offset = pak
+        .windows(path.len())
+        .rposition(|x| x == path)
+        .map(|x| x + path.len())
+        .unwrap();
+    pak[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    let err = oodle_safe::unreal::pak::PakReader::new(Cursor::new(&pak))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // Data past the end of the pak is rejected with the index.
+    let mut pak = build_pak(8, 5, &files);
+    pak[offset + 8..offset + 16].copy_from_slice(&(1u64 << 40).to_le_bytes());
+
+    let err = oodle_safe::unreal::pak::PakReader::new(Cursor::new(&pak))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // A forged decompressed size is rejected before allocating it.
+    let mut pak = build_pak(8, 5, &files);
+    let path = b"Game/Content/data.uasset\0";
+    let offset = pak
+        .windows(path.len())
+        .rposition(|x| x == path)
+        .map(|x| x + path.len())
+        .unwrap();
+    pak[offset + 16..offset + 24].copy_from_slice(&(1u64 << 40).to_le_bytes());
+
+    let mut reader = oodle_safe::unreal::pak::PakReader::new(Cursor::new(&pak)).unwrap();
+    let err = reader.read("Game/Content/data.uasset").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+/// Build the TOC and the single partition of an IoStore container, whose
+/// chunks start at block boundaries. Chunks are compressed with Oodle Kraken,
+/// except for their blocks marked as stored.
+#[cfg(feature = "unreal")]
+fn build_iostore(
+    version: u8,
+    chunks: &[(oodle_safe::unreal::iostore::ChunkId, &[u8], bool)],
+    block_size: usize,
+) -> (Vec<u8>, Vec<u8>) {
+    let mut cas = Vec::new();
+    let mut offsets_and_lengths = Vec::new();
+    let mut blocks = Vec::new();
+    let mut stream_len = 0u64;
+
+    for (_, data, compressed) in chunks {
+        offsets_and_lengths.extend_from_slice(&stream_len.to_be_bytes()[3..]);
+        offsets_and_lengths.extend_from_slice(&(data.len() as u64).to_be_bytes()[3..]);
+
+        for chunk in data.chunks(block_size) {
+            let (stored, method) = if *compressed {
+                let stored = oodle_safe::compress_to_vec(
+                    oodle_safe::Compressor::Kraken,
+                    chunk,
+                    oodle_safe::CompressionLevel::Fast,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap();
+                (stored, 1u8)
+            } else {
+                (chunk.to_vec(), 0u8)
+            };
+
+            blocks.extend_from_slice(&(cas.len() as u64).to_le_bytes()[..5]);
+            blocks.extend_from_slice(&(stored.len() as u32).to_le_bytes()[..3]);
+            blocks.extend_from_slice(&(chunk.len() as u32).to_le_bytes()[..3]);
+            blocks.push(method);
+            cas.extend_from_slice(&stored);
+        }
+
+        stream_len += data.len().next_multiple_of(block_size) as u64;
+    }
+
+    let mut toc = Vec::new();
+    toc.extend_from_slice(&oodle_safe::unreal::iostore::MAGIC);
+    toc.push(version);
+    toc.extend_from_slice(&[0u8; 3]);
+    toc.extend_from_slice(&144u32.to_le_bytes());
+    toc.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    toc.extend_from_slice(&(blocks.len() as u32 / 12).to_le_bytes());
+    toc.extend_from_slice(&12u32.to_le_bytes());
+    toc.extend_from_slice(&1u32.to_le_bytes());
+    toc.extend_from_slice(&32u32.to_le_bytes());
+    toc.extend_from_slice(&(block_size as u32).to_le_bytes());
+    toc.extend_from_slice(&0u32.to_le_bytes());
+    toc.extend_from_slice(&1u32.to_le_bytes());
+    toc.extend_from_slice(&0x1234u64.to_le_bytes());
+    toc.extend_from_slice(&[0u8; 16]);
+    toc.push(0x01);
+    toc.extend_from_slice(&[0u8; 3]);
+    toc.extend_from_slice(&0u32.to_le_bytes());
+    toc.extend_from_slice(&u64::MAX.to_le_bytes());
+    toc.extend_from_slice(&0u32.to_le_bytes());
+    toc.extend_from_slice(&[0u8; 4 + 5 * 8]);
+    assert_eq!(toc.len(), 144);
+
+    for (id, _, _) in chunks {
+        toc.extend_from_slice(&id.0);
+    }
+    toc.extend_from_slice(&offsets_and_lengths);
+    toc.extend_from_slice(&blocks);
+
+    let mut name = [0u8; 32];
+    name[..5].copy_from_slice(b"Oodle");
+    toc.extend_from_slice(&name);
+
+    // Chunk metadata, which is not read.
+    toc.extend_from_slice(&vec![0u8; chunks.len() * 33]);
+
+    (toc, cas)
+}
+
+#[cfg(feature = "unreal")]
+#[test]
+fn test_unreal_iostore() {
+    use oodle_safe::unreal::iostore::{ChunkId, IoStoreReader};
+    use std::io::Cursor;
+
+    let data = generate_data(150_000);
+    let decompressed = include_bytes!("../test_data/decompressed");
+
+    let id = |x: u8| ChunkId([x, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+    let chunks: [(ChunkId, &[u8], bool); 3] = [
+        (id(1), &data, true),
+        (id(2), b"stored chunk", false),
+        (id(3), decompressed, true),
+    ];
+
+    for version in [2, 3, 5, 8] {
+        let (toc, cas) = build_iostore(version, &chunks, 0x10000);
+
+        let mut reader = IoStoreReader::new(toc.as_slice(), Cursor::new(&cas))
+            .unwrap_or_else(|err| panic!("invalid container version {}: {}", version, err));
+        assert_eq!(reader.version(), version);
+        assert_eq!(reader.entries().len(), chunks.len());
+        assert_eq!(reader.blocks().len(), 5);
+        assert_eq!(reader.block_range(&id(1)), Some(0..3));
+        assert_eq!(reader.block_range(&id(3)), Some(4..5));
+        assert_eq!(
+            reader.blocks()[0].compression_method.as_deref(),
+            Some("Oodle")
+        );
+
+        for (id, content, _) in chunks {
+            let output = reader
+                .read(&id)
+                .unwrap_or_else(|err| panic!("chunk {} in version {}: {}", id, version, err));
+            assert_eq!(output, content);
+        }
+
+        let err = reader.read(&id(4)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        // A truncated partition is reported from the block sizes of the TOC.
+        let err = IoStoreReader::new(toc.as_slice(), Cursor::new(&cas[..cas.len() - 1]))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("compression block 4"));
+    }
+
+    // A short block before the last one of a chunk is rejected.
+    let (mut toc, cas) = build_iostore(8, &chunks, 0x10000);
+    let blocks_start = 144 + chunks.len() * (12 + 10);
+    toc[blocks_start + 8..blocks_start + 11].copy_from_slice(&0x8000u32.to_le_bytes()[..3]);
+
+    let mut reader = IoStoreReader::new(toc.as_slice(), Cursor::new(&cas)).unwrap();
+    let err = reader.read(&id(1)).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("compression block 0"));
+
+    assert_eq!(id(1).to_string(), "010000000000000000000002");
+}
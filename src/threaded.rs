@@ -0,0 +1,179 @@
+use std::sync::mpsc;
+
+use crate::{
+    decompress, CheckCRC, Compressor, DecodeThreadPhase, OodleError, SeekTable, BLOCK_LEN, FAILED,
+};
+
+/// Output buffer shared between the two decode threads.
+///
+/// Phase 1 and phase 2 never run on the same block at the same time, and each
+/// block is written to its own part of the buffer.
+#[derive(Clone, Copy)]
+pub(crate) struct Output {
+    pub(crate) ptr: *mut u8,
+    pub(crate) len: usize,
+}
+
+unsafe impl Send for Output {}
+
+/// Decompress Kraken data with the two-phase threaded decoder.
+///
+/// Each block is decoded in two steps sharing the same decoder memory: phase
+/// 1 runs on the calling thread and phase 2 on a second thread, so that
+/// phase 1 of a block overlaps with phase 2 of the previous one.
+///
+/// The result is the same as [decompress](crate::decompress) without a
+/// dictionary. Data which is not compressed with Kraken goes through
+/// [decompress](crate::decompress) on the calling thread. Kraken data which
+/// fails to decode returns [OodleError::CorruptData].
+///
+/// # Example
+///
+/// ```rust
+/// let compressed = include_bytes!("../test_data/compressed");
+/// # let decompressed_size = u32::from_le_bytes(compressed[..4].try_into().unwrap()) as usize;
+///
+/// let mut decompressed = vec![0u8; decompressed_size];
+///
+/// oodle_safe::decompress_threaded(&compressed[4..], &mut decompressed, None)
+///     .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+/// ```
+pub fn decompress_threaded(
+    compressed: &[u8],
+    decompressed: &mut [u8],
+    check_crc: Option<CheckCRC>,
+) -> Result<usize, OodleError> {
+    if decompressed.is_empty() {
+        return Ok(0);
+    }
+
+    let blocks = match SeekTable::new(compressed, decompressed.len() as u64, BLOCK_LEN) {
+        Ok(x) if x.compressor() == Some(Compressor::Kraken) => x,
+        _ => return decompress(compressed, decompressed, None, check_crc, None, None),
+    };
+
+    let check_crc = check_crc.unwrap_or_default();
+    let output = Output {
+        ptr: decompressed.as_mut_ptr(),
+        len: decompressed.len(),
+    };
+
+    if decode_phased(compressed, output, &blocks, check_crc) {
+        Ok(decompressed.len())
+    } else {
+        Err(OodleError::CorruptData)
+    }
+}
+
+/// Decode all the blocks of `compressed`, returning whether it succeeded.
+pub(crate) fn decode_phased(
+    compressed: &[u8],
+    output: Output,
+    blocks: &SeekTable,
+    check_crc: CheckCRC,
+) -> bool {
+    let memory_len = unsafe { oodle_sys::OodleLZ_ThreadPhased_BlockDecoderMemorySizeNeeded() };
+
+    // Phase 1 fills one decoder memory while phase 2 drains the other.
+    let (free_tx, free_rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel::<(usize, Vec<u8>)>();
+    for _ in 0..2 {
+        free_tx.send(vec![0u8; memory_len as usize]).unwrap();
+    }
+
+    std::thread::scope(|scope| {
+        let phase_two = scope.spawn(move || {
+            for (entry, mut memory) in ready_rx {
+                if !decode_block(
+                    compressed,
+                    output,
+                    blocks,
+                    entry,
+                    &mut memory,
+                    check_crc,
+                    DecodeThreadPhase::Two,
+                ) {
+                    return false;
+                }
+
+                // Phase 1 may have stopped early and dropped its receiver.
+                let _ = free_tx.send(memory);
+            }
+
+            true
+        });
+
+        let mut phase_one = true;
+        for entry in 0..blocks.num_seek_chunks() {
+            // Phase 2 stopped after a failure.
+            let Ok(mut memory) = free_rx.recv() else {
+                break;
+            };
+
+            if !decode_block(
+                compressed,
+                output,
+                blocks,
+                entry,
+                &mut memory,
+                check_crc,
+                DecodeThreadPhase::One,
+            ) {
+                phase_one = false;
+                break;
+            }
+
+            if ready_tx.send((entry, memory)).is_err() {
+                break;
+            }
+        }
+        drop(ready_tx);
+
+        let phase_two = phase_two.join().unwrap();
+        phase_one && phase_two
+    })
+}
+
+/// Run one phase of the decoding of the block `entry`.
+fn decode_block(
+    compressed: &[u8],
+    output: Output,
+    blocks: &SeekTable,
+    entry: usize,
+    memory: &mut [u8],
+    check_crc: CheckCRC,
+    thread_phase: DecodeThreadPhase,
+) -> bool {
+    let compressed_range = blocks.compressed_range(entry);
+    let decompressed_range = blocks.decompressed_range(entry);
+    let compressed = &compressed[compressed_range.start as usize..compressed_range.end as usize];
+    let block_len = (decompressed_range.end - decompressed_range.start) as usize;
+
+    // Only phase 2 writes the decompressed data.
+    let writes_output = matches!(thread_phase, DecodeThreadPhase::Two);
+
+    // The whole output is the decode window, so that matches can reach into
+    // the previous blocks.
+    let result = unsafe {
+        oodle_sys::OodleLZ_Decompress(
+            compressed.as_ptr() as *const _,
+            compressed.len() as isize,
+            output.ptr.add(decompressed_range.start as usize) as *mut _,
+            block_len as isize,
+            oodle_sys::OodleLZ_FuzzSafe_OodleLZ_FuzzSafe_Yes,
+            check_crc.into(),
+            oodle_sys::OodleLZ_Verbosity_OodleLZ_Verbosity_None,
+            output.ptr as *mut _,
+            output.len as isize,
+            None,
+            std::ptr::null_mut(),
+            memory.as_mut_ptr() as *mut _,
+            memory.len() as isize,
+            thread_phase.into(),
+        )
+    };
+
+    // With a decode window, the result counts from its base rather than from
+    // the start of the block.
+    result != FAILED as isize && (!writes_output || result as u64 == decompressed_range.end)
+}
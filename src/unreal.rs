@@ -0,0 +1,45 @@
+//! Readers for Unreal Engine containers holding Oodle-compressed data.
+//!
+//! Only the Oodle compression method is decoded. Entries compressed with
+//! other methods, or encrypted, are reported as
+//! [Unsupported](std::io::ErrorKind::Unsupported).
+
+pub mod iostore;
+pub mod pak;
+
+use crate::bytes::ByteReader;
+use crate::OodleError;
+
+/// Read a serialized `FString`: an i32 length including the null terminator,
+/// positive for Latin-1 and negative for UTF-16 characters.
+fn read_fstring(reader: &mut ByteReader) -> Result<String, OodleError> {
+    let len = reader.i32()?;
+
+    let string: String = if len >= 0 {
+        let bytes = reader.bytes(len as usize)?;
+        bytes.iter().map(|x| *x as char).collect()
+    } else {
+        let units = reader.bytes(len.unsigned_abs() as usize * 2)?;
+        let units = units
+            .chunks_exact(2)
+            .map(|x| u16::from_le_bytes([x[0], x[1]]));
+        char::decode_utf16(units)
+            .collect::<Result<_, _>>()
+            .map_err(|_| OodleError::InvalidFormat("invalid UTF-16 string"))?
+    };
+
+    match string.strip_suffix('\0') {
+        Some(x) => Ok(x.to_owned()),
+        None if string.is_empty() => Ok(string),
+        None => Err(OodleError::InvalidFormat("string is not null-terminated")),
+    }
+}
+
+/// Whether `method` names the Oodle compression method.
+fn is_oodle(method: &str) -> bool {
+    method.eq_ignore_ascii_case("oodle")
+}
+
+fn unsupported(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Unsupported, message)
+}
@@ -0,0 +1,460 @@
+//! Reader for Unreal Engine IoStore containers, made of a `.utoc` table of
+//! contents and one or more `.ucas` partitions holding the data.
+//!
+//! The data of all the chunks forms a single stream split into compression
+//! blocks of a fixed decompressed size, each compressed with the method named
+//! by its TOC entry. TOC versions 1 to 8 are supported. Encrypted containers
+//! are not supported.
+
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use super::{is_oodle, unsupported};
+use crate::bytes::ByteReader;
+use crate::OodleError;
+
+/// Magic number at the start of a TOC.
+pub const MAGIC: [u8; 16] = *b"-==--==--==--==-";
+
+/// Last TOC version supported by this crate.
+pub const MAX_VERSION: u8 = 8;
+
+const VERSION_PARTITION_SIZE: u8 = 3;
+const VERSION_PERFECT_HASH: u8 = 4;
+const VERSION_PERFECT_HASH_WITH_OVERFLOW: u8 = 5;
+
+const HEADER_LEN: usize = 144;
+const CHUNK_ID_LEN: usize = 12;
+const OFFSET_AND_LENGTH_LEN: usize = 10;
+const COMPRESSED_BLOCK_LEN: usize = 12;
+
+const CONTAINER_ENCRYPTED: u8 = 0x02;
+const CONTAINER_SIGNED: u8 = 0x04;
+
+/// Identifier of a chunk of an IoStore container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkId(pub [u8; 12]);
+
+impl ChunkId {
+    /// Type of the chunk, as a raw `EIoChunkType` value.
+    pub fn chunk_type(&self) -> u8 {
+        self.0[11]
+    }
+}
+
+impl std::fmt::Display for ChunkId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|x| write!(f, "{:02x}", x))
+    }
+}
+
+/// Entry of the TOC, locating a chunk in the decompressed stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TocEntry {
+    /// Identifier of the chunk.
+    pub id: ChunkId,
+
+    /// Position of the chunk in the decompressed stream.
+    pub offset: u64,
+
+    /// Size of the decompressed data of the chunk.
+    pub len: u64,
+}
+
+/// Compression block of an IoStore container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedBlock {
+    /// Position of the block across the `.ucas` partitions.
+    pub offset: u64,
+
+    /// Size of the stored data of the block.
+    pub compressed_len: u32,
+
+    /// Size of the decompressed data of the block.
+    pub decompressed_len: u32,
+
+    /// Name of the compression method, or `None` if the block is stored
+    /// uncompressed.
+    pub compression_method: Option<String>,
+}
+
+/// Reader over an IoStore container, giving access to its chunks by
+/// [ChunkId].
+///
+/// Works the same on files and on memory, through [std::io::Cursor].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let toc = std::fs::File::open("global.utoc").unwrap();
+/// let cas = std::fs::File::open("global.ucas").unwrap();
+///
+/// let mut container = oodle_safe::unreal::iostore::IoStoreReader::new(toc, cas)
+///     .unwrap_or_else(|err| panic!("invalid container: {}", err));
+///
+/// for entry in container.entries().to_vec() {
+///     let data = container
+///         .read(&entry.id)
+///         .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+/// }
+/// ```
+pub struct IoStoreReader<R: Read + Seek> {
+    partitions: Vec<R>,
+    partition_size: u64,
+    version: u8,
+    compression_block_size: u32,
+    entries: Vec<TocEntry>,
+    ids: HashMap<ChunkId, usize>,
+    blocks: Vec<CompressedBlock>,
+}
+
+impl<R: Read + Seek> IoStoreReader<R> {
+    /// Create a reader over the container described by the TOC read from
+    /// `toc`, whose data is in the single partition `cas`.
+    pub fn new(toc: impl Read, cas: R) -> std::io::Result<Self> {
+        Self::with_partitions(toc, vec![cas])
+    }
+
+    /// Create a reader over the container described by the TOC read from
+    /// `toc`, whose data is split into `partitions`, in order.
+    ///
+    /// Returns an [InvalidData](std::io::ErrorKind::InvalidData) error if the
+    /// TOC is malformed, or if a compression block of the TOC does not fit in
+    /// its partition, as for a truncated container. Returns an
+    /// [Unsupported](std::io::ErrorKind::Unsupported) error if the container
+    /// is encrypted.
+    pub fn with_partitions(mut toc: impl Read, mut partitions: Vec<R>) -> std::io::Result<Self> {
+        let mut bytes = Vec::new();
+        toc.read_to_end(&mut bytes)?;
+
+        let mut reader = ByteReader::new(&bytes);
+        if reader.array::<16>()? != MAGIC {
+            return Err(OodleError::InvalidFormat("bad TOC magic").into());
+        }
+
+        let version = reader.u8()?;
+        if version == 0 || version > MAX_VERSION {
+            return Err(OodleError::InvalidFormat("unsupported TOC version").into());
+        }
+
+        let _reserved = reader.array::<3>()?;
+        let header_len = reader.u32()? as usize;
+        let num_entries = reader.u32()? as usize;
+        let num_blocks = reader.u32()? as usize;
+        let block_entry_len = reader.u32()? as usize;
+        let num_compression_methods = reader.u32()? as usize;
+        let compression_method_len = reader.u32()? as usize;
+        let compression_block_size = reader.u32()?;
+        let directory_index_len = reader.u32()? as usize;
+        let partition_count = reader.u32()?;
+        let _container_id = reader.u64()?;
+        let _encryption_key_guid = reader.array::<16>()?;
+        let container_flags = reader.u8()?;
+        let _reserved = reader.array::<3>()?;
+        let num_perfect_hash_seeds = reader.u32()? as usize;
+        let partition_size = reader.u64()?;
+        let num_without_perfect_hash = reader.u32()? as usize;
+
+        if header_len < HEADER_LEN || block_entry_len != COMPRESSED_BLOCK_LEN {
+            return Err(OodleError::InvalidFormat("unsupported TOC header").into());
+        }
+        if container_flags & CONTAINER_ENCRYPTED != 0 {
+            return Err(unsupported(
+                "encrypted IoStore containers are not supported".into(),
+            ));
+        }
+        if compression_block_size == 0 {
+            return Err(OodleError::InvalidFormat("TOC compression block size is 0").into());
+        }
+
+        let (partition_count, partition_size) = if version >= VERSION_PARTITION_SIZE {
+            let partition_size = match partition_size {
+                0 => u64::MAX,
+                x => x,
+            };
+            (partition_count.max(1) as usize, partition_size)
+        } else {
+            (1, u64::MAX)
+        };
+        if partitions.len() != partition_count {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "container has {} partitions, but {} were given",
+                    partition_count,
+                    partitions.len()
+                ),
+            ));
+        }
+
+        let mut reader = ByteReader::new(bytes.get(header_len..).unwrap_or_default());
+
+        let ids = reader.bytes(num_entries * CHUNK_ID_LEN)?;
+        let offsets_and_lengths = reader.bytes(num_entries * OFFSET_AND_LENGTH_LEN)?;
+
+        if version >= VERSION_PERFECT_HASH {
+            let _seeds = reader.bytes(num_perfect_hash_seeds * 4)?;
+        }
+        if version >= VERSION_PERFECT_HASH_WITH_OVERFLOW {
+            let _without_perfect_hash = reader.bytes(num_without_perfect_hash * 4)?;
+        }
+
+        let block_entries = reader.bytes(num_blocks * COMPRESSED_BLOCK_LEN)?;
+
+        let mut compression_methods = Vec::new();
+        for _ in 0..num_compression_methods {
+            let name = reader.bytes(compression_method_len)?;
+            let len = name.iter().position(|x| *x == 0).unwrap_or(name.len());
+            compression_methods.push(name[..len].iter().map(|x| *x as char).collect::<String>());
+        }
+
+        // The signatures, the directory index and the chunk metadata that
+        // follow are not needed to read the chunks.
+        if container_flags & CONTAINER_SIGNED != 0 {
+            let hash_len = reader.u32()? as usize;
+            let _signatures = reader.bytes(hash_len * 2)?;
+            let _block_hashes = reader.bytes(num_blocks * 20)?;
+        }
+        let _directory_index = reader.bytes(directory_index_len)?;
+
+        let mut entries = Vec::with_capacity(num_entries);
+        for (id, offset_and_length) in ids
+            .chunks_exact(CHUNK_ID_LEN)
+            .zip(offsets_and_lengths.chunks_exact(OFFSET_AND_LENGTH_LEN))
+        {
+            entries.push(TocEntry {
+                id: ChunkId(id.try_into().unwrap()),
+                offset: read_uint(&offset_and_length[..5], true),
+                len: read_uint(&offset_and_length[5..], true),
+            });
+        }
+
+        let mut blocks = Vec::with_capacity(num_blocks);
+        for entry in block_entries.chunks_exact(COMPRESSED_BLOCK_LEN) {
+            let compression_method = match entry[11] as usize {
+                0 => None,
+                x => Some(
+                    compression_methods
+                        .get(x - 1)
+                        .cloned()
+                        .ok_or(OodleError::InvalidFormat("unknown TOC compression method"))?,
+                ),
+            };
+
+            blocks.push(CompressedBlock {
+                offset: read_uint(&entry[..5], false),
+                compressed_len: read_uint(&entry[5..8], false) as u32,
+                decompressed_len: read_uint(&entry[8..11], false) as u32,
+                compression_method,
+            });
+        }
+
+        let mut partition_lens = Vec::with_capacity(partitions.len());
+        for partition in &mut partitions {
+            partition_lens.push(partition.seek(SeekFrom::End(0))?);
+        }
+
+        for (index, block) in blocks.iter().enumerate() {
+            if block.decompressed_len > compression_block_size
+                || (block.compression_method.is_none()
+                    && block.compressed_len != block.decompressed_len)
+            {
+                return Err(invalid_data(format!(
+                    "TOC compression block {} has invalid sizes",
+                    index
+                )));
+            }
+
+            let partition = (block.offset / partition_size) as usize;
+            let start = block.offset % partition_size;
+            let end = start + block.compressed_len as u64;
+
+            match partition_lens.get(partition) {
+                Some(len) if end <= *len => {}
+                Some(len) => {
+                    return Err(invalid_data(format!(
+                        "compression block {} ends at byte {} of partition {}, \
+                         which is only {} bytes long",
+                        index, end, partition, len
+                    )))
+                }
+                None => {
+                    return Err(invalid_data(format!(
+                        "compression block {} is in missing partition {}",
+                        index, partition
+                    )))
+                }
+            }
+        }
+
+        for entry in &entries {
+            let range = block_range(entry, compression_block_size);
+            if range.end > blocks.len() {
+                return Err(invalid_data(format!(
+                    "chunk {} needs compression block {}, but the TOC has {}",
+                    entry.id,
+                    range.end - 1,
+                    blocks.len()
+                )));
+            }
+        }
+
+        let ids = entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (entry.id, index))
+            .collect();
+
+        Ok(Self {
+            partitions,
+            partition_size,
+            version,
+            compression_block_size,
+            entries,
+            ids,
+            blocks,
+        })
+    }
+
+    /// Version of the TOC.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Decompressed size of each compression block.
+    pub fn compression_block_size(&self) -> u32 {
+        self.compression_block_size
+    }
+
+    /// Entries of the TOC, in order.
+    pub fn entries(&self) -> &[TocEntry] {
+        &self.entries
+    }
+
+    /// Entry of the chunk `id`, if any.
+    pub fn entry(&self, id: &ChunkId) -> Option<&TocEntry> {
+        self.ids.get(id).map(|index| &self.entries[*index])
+    }
+
+    /// Compression blocks of the container, in order.
+    pub fn blocks(&self) -> &[CompressedBlock] {
+        &self.blocks
+    }
+
+    /// Indices of the compression blocks holding the chunk `id`, if any.
+    pub fn block_range(&self, id: &ChunkId) -> Option<Range<usize>> {
+        self.entry(id)
+            .map(|entry| block_range(entry, self.compression_block_size))
+    }
+
+    /// Get a reference to the `.ucas` partitions.
+    pub fn partitions(&self) -> &[R] {
+        &self.partitions
+    }
+
+    /// Read and decompress the chunk `id`, block by block.
+    ///
+    /// Returns a [NotFound](std::io::ErrorKind::NotFound) error if the
+    /// container has no such chunk, and an
+    /// [Unsupported](std::io::ErrorKind::Unsupported) error if a block is
+    /// compressed with another method than Oodle.
+    pub fn read(&mut self, id: &ChunkId) -> std::io::Result<Vec<u8>> {
+        let entry = *self.entry(id).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no chunk {} in container", id),
+            )
+        })?;
+
+        if entry.len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let range = block_range(&entry, self.compression_block_size);
+        let mut decompressed = Vec::new();
+        let mut compressed = Vec::new();
+
+        for index in range.clone() {
+            let block = &self.blocks[index];
+
+            // The chunk is located assuming full blocks before the last one.
+            if index + 1 < range.end && block.decompressed_len != self.compression_block_size {
+                return Err(invalid_data(format!(
+                    "compression block {} has {} bytes instead of {}",
+                    index, block.decompressed_len, self.compression_block_size
+                )));
+            }
+
+            let partition = &mut self.partitions[(block.offset / self.partition_size) as usize];
+            compressed.resize(block.compressed_len as usize, 0);
+            partition.seek(SeekFrom::Start(block.offset % self.partition_size))?;
+            partition.read_exact(&mut compressed)?;
+
+            let start = decompressed.len();
+            decompressed.resize(start + block.decompressed_len as usize, 0);
+            let output = &mut decompressed[start..];
+
+            match &block.compression_method {
+                None => output.copy_from_slice(&compressed),
+                Some(method) if is_oodle(method) => {
+                    let len = crate::decompress(&compressed, output, None, None, None, None)?;
+                    if len != output.len() {
+                        return Err(invalid_data(format!(
+                            "compression block {} decompressed to {} bytes instead of {}",
+                            index,
+                            len,
+                            output.len()
+                        )));
+                    }
+                }
+                Some(method) => {
+                    return Err(unsupported(format!(
+                        "compression block {} is compressed with {}",
+                        index, method
+                    )))
+                }
+            }
+        }
+
+        let start =
+            (entry.offset - range.start as u64 * self.compression_block_size as u64) as usize;
+        let end = start + entry.len as usize;
+        if end > decompressed.len() {
+            return Err(invalid_data(format!(
+                "chunk {} is larger than its compression blocks",
+                id
+            )));
+        }
+
+        decompressed.truncate(end);
+        decompressed.drain(..start);
+        Ok(decompressed)
+    }
+}
+
+/// Indices of the compression blocks holding the data of `entry`.
+fn block_range(entry: &TocEntry, compression_block_size: u32) -> Range<usize> {
+    let block_size = compression_block_size as u64;
+    let start = entry.offset / block_size;
+    let end = if entry.len == 0 {
+        start
+    } else {
+        (entry.offset + entry.len).div_ceil(block_size)
+    };
+    start as usize..end as usize
+}
+
+/// Read an unsigned integer of up to 8 bytes.
+fn read_uint(bytes: &[u8], big_endian: bool) -> u64 {
+    let fold = |value: u64, byte: &u8| (value << 8) | *byte as u64;
+    if big_endian {
+        bytes.iter().fold(0, fold)
+    } else {
+        bytes.iter().rev().fold(0, fold)
+    }
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,645 @@
+//! Reader for Unreal Engine `.pak` files.
+//!
+//! Pak versions 1 to 11 are supported, which covers Unreal Engine 4.0 to
+//! 5.x, including the path hash index of version 10 and later. Encrypted
+//! indices and frozen indices are not supported.
+
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use super::{is_oodle, read_fstring, unsupported};
+use crate::bytes::ByteReader;
+use crate::{max_decompressed_len, OodleError};
+
+/// Magic number of the pak footer.
+pub const MAGIC: u32 = 0x5A6F12E1;
+
+/// Last pak version supported by this crate.
+pub const MAX_VERSION: u32 = 11;
+
+const VERSION_NO_TIMESTAMPS: u32 = 2;
+const VERSION_COMPRESSION_ENCRYPTION: u32 = 3;
+const VERSION_INDEX_ENCRYPTION: u32 = 4;
+const VERSION_RELATIVE_CHUNK_OFFSETS: u32 = 5;
+const VERSION_NAME_BASED_COMPRESSION: u32 = 8;
+const VERSION_FROZEN_INDEX: u32 = 9;
+const VERSION_PATH_HASH_INDEX: u32 = 10;
+
+/// Size of the footer fields from the magic number to the index hash.
+const FOOTER_BASE_LEN: usize = 44;
+
+/// Size of a compression method name in the footer.
+const COMPRESSION_NAME_LEN: usize = 32;
+
+/// Largest footer, of a version 9 pak.
+const MAX_FOOTER_LEN: usize = 16 + 1 + FOOTER_BASE_LEN + 1 + 5 * COMPRESSION_NAME_LEN;
+
+/// Legacy compression flags of the entries of paks before version 8.
+const COMPRESS_ZLIB: u32 = 0x01;
+const COMPRESS_GZIP: u32 = 0x02;
+const COMPRESS_CUSTOM: u32 = 0x04;
+
+/// Entry of a pak file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakEntry {
+    /// Path of the entry, relative to the mount point of the pak.
+    pub path: String,
+
+    /// Position of the entry record preceding the data in the pak.
+    pub offset: u64,
+
+    /// Size of the stored data of the entry.
+    pub compressed_len: u64,
+
+    /// Size of the decompressed data of the entry.
+    pub decompressed_len: u64,
+
+    /// Name of the compression method, or `None` if the entry is stored
+    /// uncompressed.
+    ///
+    /// Paks before version 8 only flag the method, and their custom method is
+    /// reported as `Oodle`.
+    pub compression_method: Option<String>,
+
+    /// Decompressed size of each compression block but the last one.
+    pub compression_block_size: u32,
+
+    /// Ranges of the stored data of each compression block in the pak, or of
+    /// the whole data if the entry is uncompressed.
+    pub blocks: Vec<Range<u64>>,
+
+    /// Whether the data of the entry is encrypted.
+    pub encrypted: bool,
+}
+
+/// Reader over a pak file, giving access to its entries by path.
+///
+/// Works the same on files and on memory, through [std::io::Cursor].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let file = std::fs::File::open("pakchunk0-Windows.pak").unwrap();
+///
+/// let mut pak = oodle_safe::unreal::pak::PakReader::new(file)
+///     .unwrap_or_else(|err| panic!("invalid pak: {}", err));
+///
+/// for entry in pak.entries().to_vec() {
+///     let data = pak
+///         .read(&entry.path)
+///         .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+/// }
+/// ```
+pub struct PakReader<R: Read + Seek> {
+    inner: R,
+    version: u32,
+    mount_point: String,
+    entries: Vec<PakEntry>,
+    paths: HashMap<String, usize>,
+}
+
+/// Footer of a pak file.
+struct Footer {
+    version: u32,
+    index_offset: u64,
+    index_len: u64,
+    compression_methods: Vec<String>,
+}
+
+impl<R: Read + Seek> PakReader<R> {
+    /// Create a reader over the pak in `inner`, reading its footer and index.
+    ///
+    /// Returns an [InvalidData](std::io::ErrorKind::InvalidData) error if the
+    /// pak is malformed or truncated, or if its version or index layout is
+    /// not supported.
+    pub fn new(mut inner: R) -> std::io::Result<Self> {
+        let pak_len = inner.seek(SeekFrom::End(0))?;
+
+        let footer_len = (pak_len as usize).min(MAX_FOOTER_LEN);
+        let mut footer = vec![0u8; footer_len];
+        inner.seek(SeekFrom::Start(pak_len - footer_len as u64))?;
+        inner.read_exact(&mut footer)?;
+
+        let footer = parse_footer(&footer)?;
+
+        let index_end = footer
+            .index_offset
+            .checked_add(footer.index_len)
+            .filter(|x| *x <= pak_len)
+            .ok_or(OodleError::InvalidFormat("pak index is out of bounds"))?;
+
+        let mut index = vec![0u8; (index_end - footer.index_offset) as usize];
+        inner.seek(SeekFrom::Start(footer.index_offset))?;
+        inner.read_exact(&mut index)?;
+
+        let mut reader = ByteReader::new(&index);
+        let mount_point = read_fstring(&mut reader)?;
+
+        let entries = if footer.version >= VERSION_PATH_HASH_INDEX {
+            read_path_hash_index(&mut inner, &mut reader, &footer, pak_len)?
+        } else {
+            read_legacy_index(&mut reader, &footer)?
+        };
+
+        if entries
+            .iter()
+            .flat_map(|x| &x.blocks)
+            .any(|x| x.end > pak_len)
+        {
+            return Err(OodleError::InvalidFormat("pak entry is out of bounds").into());
+        }
+
+        let paths = entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (entry.path.clone(), index))
+            .collect();
+
+        Ok(Self {
+            inner,
+            version: footer.version,
+            mount_point,
+            entries,
+            paths,
+        })
+    }
+
+    /// Version of the pak.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Mount point of the entries, usually relative to the engine directory.
+    pub fn mount_point(&self) -> &str {
+        &self.mount_point
+    }
+
+    /// Entries of the pak, in index order.
+    pub fn entries(&self) -> &[PakEntry] {
+        &self.entries
+    }
+
+    /// Entry at `path`, if any.
+    pub fn entry(&self, path: &str) -> Option<&PakEntry> {
+        self.paths.get(path).map(|index| &self.entries[*index])
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Read and decompress the entry at `path`, block by block.
+    ///
+    /// Returns a [NotFound](std::io::ErrorKind::NotFound) error if the pak has
+    /// no such entry, and an [Unsupported](std::io::ErrorKind::Unsupported)
+    /// error if the entry is encrypted or compressed with another method than
+    /// Oodle.
+    pub fn read(&mut self, path: &str) -> std::io::Result<Vec<u8>> {
+        let entry = self.entry(path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no pak entry at {}", path),
+            )
+        })?;
+
+        if entry.encrypted {
+            return Err(unsupported(format!("pak entry {} is encrypted", path)));
+        }
+
+        let entry = entry.clone();
+        let decompressed_len = entry.decompressed_len as usize;
+
+        let Some(method) = &entry.compression_method else {
+            let data = match entry.blocks.first() {
+                Some(block) => self.read_range(block)?,
+                None => Vec::new(),
+            };
+            if data.len() != decompressed_len {
+                return Err(OodleError::InvalidFormat("pak entry has an invalid size").into());
+            }
+            return Ok(data);
+        };
+
+        if !is_oodle(method) {
+            return Err(unsupported(format!(
+                "pak entry {} is compressed with {}",
+                path, method
+            )));
+        }
+
+        let block_len = match entry.compression_block_size as usize {
+            x if x == 0 || entry.blocks.len() == 1 => decompressed_len,
+            x => x,
+        };
+
+        // Checked before anything is allocated for the entry.
+        let compressed_len = entry
+            .blocks
+            .iter()
+            .fold(0u64, |len, x| len.saturating_add(x.end - x.start));
+        if entry.decompressed_len > max_decompressed_len(compressed_len as usize) as u64 {
+            return Err(OodleError::InvalidFormat("pak entry size too large for its data").into());
+        }
+
+        let mut decompressed = vec![0u8; decompressed_len];
+        let mut decompressed_rest = decompressed.as_mut_slice();
+
+        for block in &entry.blocks {
+            if decompressed_rest.is_empty() {
+                return Err(OodleError::InvalidFormat("pak entry has too many blocks").into());
+            }
+
+            let (output, rest) =
+                decompressed_rest.split_at_mut(block_len.min(decompressed_rest.len()));
+            decompressed_rest = rest;
+
+            let compressed = self.read_range(block)?;
+            let len = crate::decompress(&compressed, output, None, None, None, None)?;
+            if len != output.len() {
+                return Err(OodleError::CorruptData.into());
+            }
+        }
+
+        if !decompressed_rest.is_empty() {
+            return Err(OodleError::InvalidFormat("pak entry has too few blocks").into());
+        }
+
+        Ok(decompressed)
+    }
+
+    fn read_range(&mut self, range: &Range<u64>) -> std::io::Result<Vec<u8>> {
+        let mut data = vec![0u8; (range.end - range.start) as usize];
+        self.inner.seek(SeekFrom::Start(range.start))?;
+        self.inner.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Parse the footer at the end of `bytes`, trying the layouts of each
+/// version.
+fn parse_footer(bytes: &[u8]) -> Result<Footer, OodleError> {
+    // Number of compression method names and whether the frozen index flag
+    // is present, for each footer layout.
+    const LAYOUTS: [(usize, bool); 4] = [(0, false), (5, false), (4, false), (5, true)];
+
+    for (names, frozen) in LAYOUTS {
+        let tail_len = FOOTER_BASE_LEN + frozen as usize + names * COMPRESSION_NAME_LEN;
+        let Some(start) = bytes.len().checked_sub(tail_len) else {
+            continue;
+        };
+
+        let mut reader = ByteReader::new(&bytes[start..]);
+        if reader.u32()? != MAGIC {
+            continue;
+        }
+
+        let version = reader.u32()?;
+        let expected = match (names, frozen) {
+            (0, _) => version < VERSION_NAME_BASED_COMPRESSION,
+            (4, _) => version == VERSION_NAME_BASED_COMPRESSION,
+            (_, true) => version == VERSION_FROZEN_INDEX,
+            _ => version == VERSION_NAME_BASED_COMPRESSION || version > VERSION_FROZEN_INDEX,
+        };
+        if !expected {
+            continue;
+        }
+
+        if version == 0 || version > MAX_VERSION {
+            return Err(OodleError::InvalidFormat("unsupported pak version"));
+        }
+
+        let index_offset = reader.u64()?;
+        let index_len = reader.u64()?;
+        let _index_hash = reader.bytes(20)?;
+
+        if frozen && reader.u8()? != 0 {
+            return Err(OodleError::InvalidFormat(
+                "frozen pak index is not supported",
+            ));
+        }
+
+        let mut compression_methods = Vec::new();
+        for _ in 0..names {
+            let name = reader.bytes(COMPRESSION_NAME_LEN)?;
+            let len = name.iter().position(|x| *x == 0).unwrap_or(name.len());
+            compression_methods.push(name[..len].iter().map(|x| *x as char).collect());
+        }
+
+        if version >= VERSION_INDEX_ENCRYPTION {
+            let encrypted_index = start
+                .checked_sub(1)
+                .map(|x| bytes[x])
+                .ok_or(OodleError::InvalidFormat("unexpected end of data"))?;
+            if encrypted_index != 0 {
+                return Err(OodleError::InvalidFormat(
+                    "encrypted pak index is not supported",
+                ));
+            }
+        }
+
+        return Ok(Footer {
+            version,
+            index_offset,
+            index_len,
+            compression_methods,
+        });
+    }
+
+    Err(OodleError::InvalidFormat("bad pak magic"))
+}
+
+/// Entry record as serialized in the index and before the data of the
+/// entries.
+#[derive(Clone)]
+struct EntryRecord {
+    offset: u64,
+    compressed_len: u64,
+    decompressed_len: u64,
+    compression_method: Option<String>,
+    compression_block_size: u32,
+    blocks: Vec<Range<u64>>,
+    encrypted: bool,
+}
+
+/// Name of the compression method of an entry record.
+fn compression_method(footer: &Footer, method: u32) -> Result<Option<String>, OodleError> {
+    if footer.version < VERSION_NAME_BASED_COMPRESSION {
+        return Ok(match method & 0x0F {
+            0 => None,
+            COMPRESS_ZLIB => Some("Zlib".to_owned()),
+            COMPRESS_GZIP => Some("Gzip".to_owned()),
+            COMPRESS_CUSTOM => Some("Oodle".to_owned()),
+            _ => return Err(OodleError::InvalidFormat("unknown pak compression flags")),
+        });
+    }
+
+    match method {
+        0 => Ok(None),
+        x => footer
+            .compression_methods
+            .get(x as usize - 1)
+            .filter(|x| !x.is_empty())
+            .cloned()
+            .map(Some)
+            .ok_or(OodleError::InvalidFormat("unknown pak compression method")),
+    }
+}
+
+/// Size of the entry record serialized before the data of an entry.
+fn record_len(version: u32, compressed: bool, num_blocks: usize) -> u64 {
+    let mut len = 8 + 8 + 8 + 4 + 20;
+    if version < VERSION_NO_TIMESTAMPS {
+        len += 8;
+    }
+    if version >= VERSION_COMPRESSION_ENCRYPTION {
+        if compressed {
+            len += 4 + 16 * num_blocks as u64;
+        }
+        len += 1 + 4;
+    }
+    len
+}
+
+/// Read a full entry record.
+fn read_record(reader: &mut ByteReader, footer: &Footer) -> Result<EntryRecord, OodleError> {
+    let offset = reader.u64()?;
+    let compressed_len = reader.u64()?;
+    let decompressed_len = reader.u64()?;
+    let compression_method = compression_method(footer, reader.u32()?)?;
+
+    if footer.version < VERSION_NO_TIMESTAMPS {
+        let _timestamp = reader.u64()?;
+    }
+    let _hash = reader.bytes(20)?;
+
+    let mut blocks = Vec::new();
+    let mut encrypted = false;
+    let mut compression_block_size = 0;
+
+    if footer.version >= VERSION_COMPRESSION_ENCRYPTION {
+        if compression_method.is_some() {
+            let num_blocks = reader.u32()?;
+            for _ in 0..num_blocks {
+                blocks.push(reader.u64()?..reader.u64()?);
+            }
+        }
+        encrypted = reader.u8()? & 0x01 != 0;
+        compression_block_size = reader.u32()?;
+    }
+
+    let base = if footer.version >= VERSION_RELATIVE_CHUNK_OFFSETS {
+        offset
+    } else {
+        0
+    };
+
+    if compression_method.is_some() {
+        for block in &mut blocks {
+            *block = absolute_range(base, block)?;
+        }
+    } else {
+        let start = absolute_range(offset, &(0..record_len(footer.version, false, 0)))?.end;
+        blocks = vec![absolute_range(start, &(0..compressed_len))?];
+    }
+
+    Ok(EntryRecord {
+        offset,
+        compressed_len,
+        decompressed_len,
+        compression_method,
+        compression_block_size,
+        blocks,
+        encrypted,
+    })
+}
+
+fn absolute_range(base: u64, range: &Range<u64>) -> Result<Range<u64>, OodleError> {
+    match (base.checked_add(range.start), base.checked_add(range.end)) {
+        (Some(start), Some(end)) if start <= end => Ok(start..end),
+        _ => Err(OodleError::InvalidFormat("invalid pak block range")),
+    }
+}
+
+impl EntryRecord {
+    fn into_entry(self, path: String) -> PakEntry {
+        PakEntry {
+            path,
+            offset: self.offset,
+            compressed_len: self.compressed_len,
+            decompressed_len: self.decompressed_len,
+            compression_method: self.compression_method,
+            compression_block_size: self.compression_block_size,
+            blocks: self.blocks,
+            encrypted: self.encrypted,
+        }
+    }
+}
+
+/// Read the index of paks before version 10, listing the path and record of
+/// each entry.
+fn read_legacy_index(
+    reader: &mut ByteReader,
+    footer: &Footer,
+) -> Result<Vec<PakEntry>, OodleError> {
+    let num_entries = reader.u32()?;
+    let mut entries = Vec::new();
+
+    for _ in 0..num_entries {
+        let path = read_fstring(reader)?;
+        entries.push(read_record(reader, footer)?.into_entry(path));
+    }
+
+    Ok(entries)
+}
+
+/// Read the index of paks since version 10, whose paths are listed in a
+/// separate full directory index and whose records are mostly bit-packed.
+fn read_path_hash_index<R: Read + Seek>(
+    inner: &mut R,
+    reader: &mut ByteReader,
+    footer: &Footer,
+    pak_len: u64,
+) -> Result<Vec<PakEntry>, std::io::Error> {
+    let _num_entries = reader.u32()?;
+    let _path_hash_seed = reader.u64()?;
+
+    if reader.u32()? != 0 {
+        let _path_hash_index_offset = reader.u64()?;
+        let _path_hash_index_len = reader.u64()?;
+        let _path_hash_index_hash = reader.bytes(20)?;
+    }
+
+    if reader.u32()? == 0 {
+        return Err(OodleError::InvalidFormat("pak has no full directory index").into());
+    }
+    let directory_offset = reader.u64()?;
+    let directory_len = reader.u64()?;
+    let _directory_hash = reader.bytes(20)?;
+
+    let encoded_len = reader.u32()? as usize;
+    let encoded = reader.bytes(encoded_len)?;
+
+    let num_records = reader.u32()?;
+    let mut records = Vec::new();
+    for _ in 0..num_records {
+        records.push(read_record(reader, footer)?);
+    }
+
+    directory_offset
+        .checked_add(directory_len)
+        .filter(|x| *x <= pak_len)
+        .ok_or(OodleError::InvalidFormat(
+            "pak directory index is out of bounds",
+        ))?;
+
+    let mut directory = vec![0u8; directory_len as usize];
+    inner.seek(SeekFrom::Start(directory_offset))?;
+    inner.read_exact(&mut directory)?;
+
+    let mut reader = ByteReader::new(&directory);
+    let mut entries = Vec::new();
+
+    let num_directories = reader.u32()?;
+    for _ in 0..num_directories {
+        let directory_name = read_fstring(&mut reader)?;
+        let directory_name = directory_name.trim_start_matches('/');
+
+        let num_files = reader.u32()?;
+        for _ in 0..num_files {
+            let path = format!("{}{}", directory_name, read_fstring(&mut reader)?);
+            let location = reader.i32()?;
+
+            let record = if location >= 0 {
+                let encoded = encoded
+                    .get(location as usize..)
+                    .ok_or(OodleError::InvalidFormat("invalid pak entry location"))?;
+                decode_record(&mut ByteReader::new(encoded), footer)?
+            } else {
+                let index = location.unsigned_abs() as usize - 1;
+                match records.get(index) {
+                    Some(x) => x.clone(),
+                    None => {
+                        return Err(OodleError::InvalidFormat("invalid pak entry location").into())
+                    }
+                }
+            };
+
+            entries.push(record.into_entry(path));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Decode a bit-packed entry record of the path hash index.
+fn decode_record(reader: &mut ByteReader, footer: &Footer) -> Result<EntryRecord, OodleError> {
+    let bits = reader.u32()?;
+
+    let compression_block_size = match bits & 0x3F {
+        0x3F => reader.u32()?,
+        x => x << 11,
+    };
+    let num_blocks = (bits >> 6) & 0xFFFF;
+    let encrypted = bits & (1 << 22) != 0;
+    let compression_method = compression_method(footer, (bits >> 23) & 0x3F)?;
+
+    let mut read_len = |is_u32: bool| {
+        if is_u32 {
+            reader.u32().map(u64::from)
+        } else {
+            reader.u64()
+        }
+    };
+
+    let offset = read_len(bits & (1 << 31) != 0)?;
+    let decompressed_len = read_len(bits & (1 << 30) != 0)?;
+    let compressed_len = if compression_method.is_some() {
+        read_len(bits & (1 << 29) != 0)?
+    } else {
+        decompressed_len
+    };
+
+    let data_start = record_len(
+        footer.version,
+        compression_method.is_some(),
+        num_blocks as usize,
+    );
+
+    let start = absolute_range(offset, &(0..data_start))?.end;
+    let blocks = if compression_method.is_none() || (num_blocks == 1 && !encrypted) {
+        vec![absolute_range(start, &(0..compressed_len))?]
+    } else {
+        let mut start = start;
+        let mut blocks = Vec::new();
+        for _ in 0..num_blocks {
+            let len = reader.u32()? as u64;
+            blocks.push(absolute_range(start, &(0..len))?);
+
+            // Encrypted blocks are padded to the AES block size.
+            let padded_len = if encrypted {
+                len.next_multiple_of(16)
+            } else {
+                len
+            };
+            start = absolute_range(start, &(0..padded_len))?.end;
+        }
+        blocks
+    };
+
+    Ok(EntryRecord {
+        offset,
+        compressed_len,
+        decompressed_len,
+        compression_method,
+        compression_block_size,
+        blocks,
+        encrypted,
+    })
+}
@@ -0,0 +1,38 @@
+// The allocator must be installed before any other Oodle call of the process,
+// so this test runs in its own binary.
+
+static ALLOCATOR: oodle_safe::CountingAllocator = oodle_safe::CountingAllocator::new();
+
+#[test]
+fn test_counting_allocator() {
+    oodle_safe::set_allocator(&ALLOCATOR).unwrap();
+    assert_eq!(
+        oodle_safe::set_allocator(&ALLOCATOR),
+        Err(oodle_safe::OodleError::PluginAlreadyInstalled)
+    );
+
+    let data = include_bytes!("../test_data/decompressed");
+
+    // Without scratch memory, the compressor allocates its own.
+    let compressed = oodle_safe::compress_to_vec(
+        oodle_safe::Compressor::Kraken,
+        data,
+        oodle_safe::CompressionLevel::Optimal1,
+        None,
+        None,
+        None,
+    )
+    .unwrap_or_else(|err| panic!("compression failed: {}", err));
+
+    let stats = ALLOCATOR.stats();
+    assert!(stats.allocations > 0);
+    assert_eq!(stats.deallocations, stats.allocations);
+    assert_eq!(stats.current_bytes, 0);
+    assert!(stats.peak_bytes > 0);
+
+    let mut decompressed = vec![0u8; data.len()];
+    oodle_safe::decompress(&compressed, &mut decompressed, None, None, None, None)
+        .unwrap_or_else(|err| panic!("decompression failed: {}", err));
+
+    assert_eq!(&decompressed, data);
+}